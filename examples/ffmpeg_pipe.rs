@@ -28,13 +28,7 @@ fn main() {
         .build()
         .expect("Failed to create videoconvert");
     
-    // Add a capsfilter to ensure a specific format
-    let filter = gst::ElementFactory::make("capsfilter")
-        .name("filter")
-        .build()
-        .expect("Failed to create capsfilter");
-    
-    // Set caps to I420 format, which ffmpeg can handle
+    // Restrict the sink to I420, which ffmpeg can handle, videoconvert negotiates it
     let width = 640;
     let height = 480;
     let framerate = 30;
@@ -45,18 +39,18 @@ fn main() {
         .field("height", height)
         .field("framerate", gst::Fraction::new(framerate, 1))
         .build();
-    filter.set_property("caps", caps);
     
     // Create output file path
     let output_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
         .join("target")
         .join("example_output.mp4");
     
-    // Create ffmpeg command that takes raw I420 input and encodes to H.264 MP4
+    // Create ffmpeg command that takes raw input and encodes to H.264 MP4. The size, rate and
+    // pixel format placeholders are filled in by the sink from the negotiated caps.
     let ffmpeg_cmd = format!(
-        "ffmpeg -y -f rawvideo -pix_fmt yuv420p -s {}x{} -r {} -i - \
+        "ffmpeg -y -f rawvideo -pix_fmt {{ffmpeg_pix_fmt}} -s {{width}}x{{height}} -r {{framerate}} -i - \
          -c:v libx264 -preset fast -crf 22 -f mp4 {}",
-        width, height, framerate, output_path.display()
+        output_path.display()
     );
     
     println!("Using ffmpeg command: {}", ffmpeg_cmd);
@@ -65,15 +59,16 @@ fn main() {
     let sink = gst::ElementFactory::make("videopipesink")
         .name("sink")
         .property("cmd", ffmpeg_cmd)
+        .property("caps", caps)
         .build()
         .expect("Failed to create videopipesink");
     
     // Add all elements to the pipeline
-    pipeline.add_many(&[&src, &convert, &filter, &sink])
+    pipeline.add_many(&[&src, &convert, &sink])
         .expect("Failed to add elements to pipeline");
     
     // Link the elements
-    gst::Element::link_many(&[&src, &convert, &filter, &sink])
+    gst::Element::link_many(&[&src, &convert, &sink])
         .expect("Failed to link elements");
     
     // Set the pipeline to the playing state
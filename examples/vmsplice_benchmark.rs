@@ -0,0 +1,108 @@
+use std::time::Instant;
+
+use gst::prelude::*;
+
+// Frames of 1080p I420 by default, override with: vmsplice_benchmark [WIDTH HEIGHT [FRAMES]]
+const DEFAULT_WIDTH: i32 = 1920;
+const DEFAULT_HEIGHT: i32 = 1080;
+const DEFAULT_FRAMES: i32 = 2000;
+
+// Configurations compared, the first one is the plain write() path
+const CONFIGS: &[(&str, bool, u32)] = &[
+    ("write", false, 0),
+    ("write, 1 MiB pipe", false, 1 << 20),
+    ("vmsplice", true, 0),
+    ("vmsplice, 1 MiB pipe", true, 1 << 20),
+];
+
+fn main() {
+    // Initialize GStreamer
+    gst::init().unwrap();
+
+    // Register the element directly
+    gstsubprocesspipe::register_element().unwrap();
+
+    let args: Vec<i32> = std::env::args()
+        .skip(1)
+        .map(|arg| arg.parse().expect("Arguments must be numbers"))
+        .collect();
+    let width = args.first().copied().unwrap_or(DEFAULT_WIDTH);
+    let height = args.get(1).copied().unwrap_or(DEFAULT_HEIGHT);
+    let frames = args.get(2).copied().unwrap_or(DEFAULT_FRAMES);
+
+    println!("Writing {} frames of {}x{} I420 to `cat > /dev/null`", frames, width, height);
+
+    for (name, vmsplice, pipe_size) in CONFIGS {
+        let (elapsed, bytes) = run(width, height, frames, *vmsplice, *pipe_size);
+        let secs = elapsed.as_secs_f64();
+        println!(
+            "{:<24} {:>8.3} s {:>10.1} MiB/s {:>8.1} frames/s",
+            name,
+            secs,
+            bytes as f64 / secs / (1024.0 * 1024.0),
+            frames as f64 / secs
+        );
+    }
+}
+
+// Run the pipeline to EOS, returning the elapsed time and the bytes written to the subprocess
+fn run(width: i32, height: i32, frames: i32, vmsplice: bool, pipe_size: u32) -> (std::time::Duration, u64) {
+    let pipeline = gst::Pipeline::new();
+
+    // A single frame repeated by imagefreeze, so that producing the frames costs next to nothing
+    let src = gst::ElementFactory::make("videotestsrc")
+        .property("num-buffers", 1)
+        .build()
+        .expect("Failed to create videotestsrc");
+
+    let caps = gst::Caps::builder("video/x-raw")
+        .field("format", "I420")
+        .field("width", width)
+        .field("height", height)
+        .build();
+    let capsfilter = gst::ElementFactory::make("capsfilter")
+        .property("caps", caps)
+        .build()
+        .expect("Failed to create capsfilter");
+
+    let freeze = gst::ElementFactory::make("imagefreeze")
+        .property("num-buffers", frames)
+        .build()
+        .expect("Failed to create imagefreeze");
+
+    // Without packing, the sink writes the memory of the frames as they come
+    let sink = gst::ElementFactory::make("videopipesink")
+        .property("cmd", "cat > /dev/null")
+        .property("sync", false)
+        .property("pack-planes", false)
+        .property("vmsplice", vmsplice)
+        .property("pipe-size", pipe_size)
+        .build()
+        .expect("Failed to create videopipesink");
+
+    pipeline.add_many(&[&src, &capsfilter, &freeze, &sink]).unwrap();
+    gst::Element::link_many(&[&src, &capsfilter, &freeze, &sink]).expect("Failed to link elements");
+
+    let start = Instant::now();
+    pipeline.set_state(gst::State::Playing).expect("Failed to set pipeline to Playing");
+
+    let bus = pipeline.bus().unwrap();
+    let msg = bus.timed_pop_filtered(gst::ClockTime::NONE, &[gst::MessageType::Eos, gst::MessageType::Error]);
+    let elapsed = start.elapsed();
+
+    if let Some(gst::MessageView::Error(err)) = msg.as_ref().map(|msg| msg.view()) {
+        panic!(
+            "Error from {:?}: {} ({})",
+            err.src().map(|s| s.path_string()),
+            err.error(),
+            err.debug().unwrap_or_default()
+        );
+    }
+
+    let stats = sink.property::<gst::Structure>("stats");
+    let bytes = stats.get::<u64>("bytes-written").unwrap();
+
+    pipeline.set_state(gst::State::Null).expect("Failed to set pipeline to Null");
+
+    (elapsed, bytes)
+}
@@ -0,0 +1,114 @@
+// Copyright (C) 2025, Rafael Caricio <rafael@caricio.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License, v2.0.
+// If a copy of the MPL was not distributed with this file, You can obtain one at
+// <https://mozilla.org/MPL/2.0/>.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Raw audio caps and sample layout conversion.
+
+use gst::glib;
+use gst_audio::{AudioFormat, AudioFormatInfo, AudioInfo, AudioLayout};
+
+/// Raw audio formats supported by the audio elements.
+pub const FORMATS: &[AudioFormat] = &[
+    AudioFormat::S8,
+    AudioFormat::U8,
+    AudioFormat::S16le,
+    AudioFormat::S16be,
+    AudioFormat::U16le,
+    AudioFormat::U16be,
+    AudioFormat::S24le,
+    AudioFormat::S24be,
+    AudioFormat::U24le,
+    AudioFormat::U24be,
+    AudioFormat::S32le,
+    AudioFormat::S32be,
+    AudioFormat::U32le,
+    AudioFormat::U32be,
+    AudioFormat::F32le,
+    AudioFormat::F32be,
+    AudioFormat::F64le,
+    AudioFormat::F64be,
+];
+
+/// Name of the equivalent raw ffmpeg demuxer, as in `ffmpeg -f s16le`.
+pub fn ffmpeg_format(format: &AudioFormatInfo) -> Option<String> {
+    // Padded formats such as S24_32 have no raw ffmpeg equivalent
+    if format.width() != format.depth() || format.width() % 8 != 0 {
+        return None;
+    }
+
+    let kind = match (format.is_float(), format.is_signed()) {
+        (true, _) => 'f',
+        (false, true) => 's',
+        (false, false) => 'u',
+    };
+    let endianness = match (format.width(), format.is_little_endian()) {
+        (8, _) => "",
+        (_, true) => "le",
+        (_, false) => "be",
+    };
+
+    Some(format!("{}{}{}", kind, format.width(), endianness))
+}
+
+/// Name of the equivalent interleaved ffmpeg `sample_fmt`, which is always in native endianness.
+pub fn ffmpeg_sample_fmt(format: &AudioFormatInfo) -> Option<&'static str> {
+    if format.width() > 8 && format.is_little_endian() != cfg!(target_endian = "little") {
+        return None;
+    }
+
+    let sample_fmt = match (format.width(), format.is_float(), format.is_signed()) {
+        (8, false, false) => "u8",
+        (16, false, true) => "s16",
+        (32, false, true) => "s32",
+        (64, false, true) => "s64",
+        (32, true, _) => "flt",
+        (64, true, _) => "dbl",
+        _ => return None,
+    };
+
+    Some(sample_fmt)
+}
+
+/// Template caps for all supported raw audio formats, in both layouts.
+pub fn caps() -> gst::Caps {
+    gst_audio::AudioCapsBuilder::new()
+        .format_list(FORMATS.iter().copied())
+        .build()
+}
+
+/// Interleaves the samples of a non-interleaved buffer.
+///
+/// The channel planes are located through the buffer's `GstAudioMeta`, or are expected one after
+/// the other when it has none.
+pub fn interleave(buffer: &gst::Buffer, info: &AudioInfo) -> Result<gst::Buffer, glib::BoolError> {
+    if info.layout() != AudioLayout::NonInterleaved {
+        return Ok(buffer.clone());
+    }
+
+    let audio_buffer = gst_audio::AudioBufferRef::from_buffer_ref_readable(buffer, info)?;
+    let width = info.bps() as usize;
+    let channels = info.channels() as usize;
+    let mut data = vec![0u8; audio_buffer.n_samples() * info.bpf() as usize];
+
+    for channel in 0..channels {
+        let plane = audio_buffer.plane_data(channel as u32)?;
+        for (i, sample) in plane.chunks_exact(width).enumerate() {
+            let pos = (i * channels + channel) * width;
+            data[pos..pos + width].copy_from_slice(sample);
+        }
+    }
+    drop(audio_buffer);
+
+    let mut interleaved = gst::Buffer::from_mut_slice(data);
+    buffer.copy_into(
+        interleaved.get_mut().unwrap(),
+        gst::BufferCopyFlags::FLAGS | gst::BufferCopyFlags::TIMESTAMPS,
+        ..,
+    )?;
+
+    Ok(interleaved)
+}
@@ -0,0 +1,316 @@
+// Copyright (C) 2025, Rafael Caricio <rafael@caricio.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License, v2.0.
+// If a copy of the MPL was not distributed with this file, You can obtain one at
+// <https://mozilla.org/MPL/2.0/>.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Subprocess command line settings shared by all elements.
+
+use gst::glib;
+use gst::prelude::*;
+use std::path::PathBuf;
+use std::process::{Child, Command, ExitStatus};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::template::Variables;
+use crate::writer::Waker;
+
+static WAIT_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+// Poll the child until it exits or the timeout expires
+pub fn wait_timeout(child: &mut Child, timeout: gst::ClockTime) -> std::io::Result<Option<ExitStatus>> {
+    let deadline = Instant::now() + Duration::from(timeout);
+
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+
+        let now = Instant::now();
+        if now >= deadline {
+            return Ok(None);
+        }
+
+        thread::sleep(WAIT_POLL_INTERVAL.min(deadline - now));
+    }
+}
+
+/// Same as [`wait_timeout`], but also stops waiting once `waker` fires.
+pub fn wait_interruptible(
+    child: &mut Child,
+    timeout: gst::ClockTime,
+    waker: &Waker,
+) -> std::io::Result<Option<ExitStatus>> {
+    let deadline = Instant::now() + Duration::from(timeout);
+
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+
+        let now = Instant::now();
+        if now >= deadline || !waker.sleep(WAIT_POLL_INTERVAL.min(deadline - now)) {
+            return Ok(None);
+        }
+    }
+}
+
+/// Stops a subprocess whose pipes were closed. It gets `grace` to exit on its own, then
+/// `kill_timeout` after `signal`, and is finally killed with SIGKILL.
+pub fn terminate(
+    child: &mut Child,
+    grace: gst::ClockTime,
+    signal: libc::c_int,
+    kill_timeout: gst::ClockTime,
+) -> std::io::Result<ExitStatus> {
+    // Nothing to signal if the process already exited, its PID may have been reused
+    if let Some(status) = child.try_wait()? {
+        return Ok(status);
+    }
+
+    if let Some(status) = wait_timeout(child, grace)? {
+        return Ok(status);
+    }
+
+    unsafe {
+        libc::kill(child.id() as libc::pid_t, signal);
+    }
+    if let Some(status) = wait_timeout(child, kill_timeout)? {
+        return Ok(status);
+    }
+
+    child.kill()?;
+    child.wait()
+}
+
+/// The command to run and its environment, from the `cmd`, `program`, `argv`, `env`,
+/// `clear-env` and `working-directory` properties.
+#[derive(Debug, Clone, Default)]
+pub struct CommandSettings {
+    pub cmd: String,
+    pub program: Option<String>,
+    pub argv: Vec<String>,
+    pub env: Vec<String>,
+    pub clear_env: bool,
+    pub working_directory: Option<String>,
+}
+
+impl CommandSettings {
+    pub fn properties() -> Vec<glib::ParamSpec> {
+        vec![
+            glib::ParamSpecString::builder("cmd")
+                .nick("Command")
+                .blurb("Shell command to run")
+                .mutable_ready()
+                .build(),
+            glib::ParamSpecString::builder("program")
+                .nick("Program")
+                .blurb("Executable to run directly without a shell (mutually exclusive with cmd)")
+                .mutable_ready()
+                .build(),
+            gst::ParamSpecArray::builder("argv")
+                .nick("Argument vector")
+                .blurb("Arguments passed to program, or the full argument vector if program is not set (mutually exclusive with cmd)")
+                .element_spec(&glib::ParamSpecString::builder("arg").build())
+                .mutable_ready()
+                .build(),
+            gst::ParamSpecArray::builder("env")
+                .nick("Environment")
+                .blurb("Environment variables for the subprocess as KEY=VALUE entries")
+                .element_spec(&glib::ParamSpecString::builder("var").build())
+                .mutable_ready()
+                .build(),
+            glib::ParamSpecBoolean::builder("clear-env")
+                .nick("Clear environment")
+                .blurb("Don't inherit the parent environment, only pass the variables from env")
+                .default_value(false)
+                .mutable_ready()
+                .build(),
+            glib::ParamSpecString::builder("working-directory")
+                .nick("Working directory")
+                .blurb("Working directory of the subprocess, defaults to the current directory")
+                .mutable_ready()
+                .build(),
+        ]
+    }
+
+    /// Sets one of the command properties. Returns false if `name` is not one of them.
+    pub fn set_property(&mut self, name: &str, value: &glib::Value) -> bool {
+        match name {
+            "cmd" => {
+                self.cmd = value.get::<Option<String>>().expect("type checked upstream").unwrap_or_default();
+            }
+            "program" => {
+                self.program = value
+                    .get::<Option<String>>()
+                    .expect("type checked upstream")
+                    .filter(|program| !program.is_empty());
+            }
+            "argv" => {
+                self.argv = value
+                    .get::<gst::Array>()
+                    .expect("type checked upstream")
+                    .iter()
+                    .map(|arg| arg.get::<String>().expect("type checked upstream"))
+                    .collect();
+            }
+            "env" => {
+                self.env = value
+                    .get::<gst::Array>()
+                    .expect("type checked upstream")
+                    .iter()
+                    .map(|var| var.get::<String>().expect("type checked upstream"))
+                    .collect();
+            }
+            "clear-env" => {
+                self.clear_env = value.get().expect("type checked upstream");
+            }
+            "working-directory" => {
+                self.working_directory = value
+                    .get::<Option<String>>()
+                    .expect("type checked upstream")
+                    .filter(|dir| !dir.is_empty());
+            }
+            _ => return false,
+        }
+
+        true
+    }
+
+    /// Gets one of the command properties, or `None` if `name` is not one of them.
+    pub fn property(&self, name: &str) -> Option<glib::Value> {
+        let value = match name {
+            "cmd" => {
+                self.cmd.to_value()
+            }
+            "program" => {
+                self.program.to_value()
+            }
+            "argv" => {
+                gst::Array::new(&self.argv).to_value()
+            }
+            "env" => {
+                gst::Array::new(&self.env).to_value()
+            }
+            "clear-env" => {
+                self.clear_env.to_value()
+            }
+            "working-directory" => {
+                self.working_directory.to_value()
+            }
+            _ => return None,
+        };
+
+        Some(value)
+    }
+
+    // Check that exactly one of the shell and exec modes is configured
+    pub fn validate(&self) -> Result<(), gst::ErrorMessage> {
+        let exec_mode = self.program.is_some() || !self.argv.is_empty();
+
+        if exec_mode && !self.cmd.is_empty() {
+            return Err(gst::error_msg!(
+                gst::ResourceError::Settings,
+                ["The cmd property can't be combined with program or argv"]
+            ));
+        }
+
+        if !exec_mode && self.cmd.is_empty() {
+            return Err(gst::error_msg!(
+                gst::ResourceError::Settings,
+                ["Command line not set"]
+            ));
+        }
+
+        self.env_vars().map(|_| ())
+    }
+
+    // Parse the KEY=VALUE entries of the env property
+    fn env_vars(&self) -> Result<Vec<(&str, &str)>, gst::ErrorMessage> {
+        self.env
+            .iter()
+            .map(|var| match var.split_once('=') {
+                Some((key, value)) if !key.is_empty() => Ok((key, value)),
+                _ => Err(gst::error_msg!(
+                    gst::ResourceError::Settings,
+                    ["Invalid environment variable {:?}, expected KEY=VALUE", var]
+                )),
+            })
+            .collect()
+    }
+
+    // Apply the configured environment on top of, or instead of, the inherited one
+    fn apply_env(&self, command: &mut Command) -> Result<(), gst::ErrorMessage> {
+        if self.clear_env {
+            command.env_clear();
+        }
+
+        command.envs(self.env_vars()?);
+        Ok(())
+    }
+
+    // Directory the subprocess runs in, defaulting to the current working directory
+    pub fn working_directory(&self) -> Result<PathBuf, gst::ErrorMessage> {
+        match &self.working_directory {
+            Some(dir) => {
+                let dir = PathBuf::from(dir);
+                if !dir.is_dir() {
+                    return Err(gst::error_msg!(
+                        gst::ResourceError::NotFound,
+                        ["Working directory {} does not exist", dir.display()]
+                    ));
+                }
+                Ok(dir)
+            }
+            None => std::env::current_dir().map_err(|e| {
+                gst::error_msg!(
+                    gst::ResourceError::Failed,
+                    ["Failed to get current directory: {}", e]
+                )
+            }),
+        }
+    }
+
+    // Build the command to spawn, either through `sh -c` or directly from program/argv, with
+    // all placeholders expanded. Also returns a human readable command line for logging.
+    pub fn command(&self, vars: &Variables) -> Result<(Command, String), gst::ErrorMessage> {
+        self.validate()?;
+
+        let expand = |template: &str, shell: bool| {
+            vars.expand(template, shell).map_err(|err| {
+                gst::error_msg!(gst::ResourceError::Settings, ["{}", err])
+            })
+        };
+
+        if !self.cmd.is_empty() {
+            let cmd = expand(&self.cmd, true)?;
+            let mut command = Command::new("sh");
+            command.arg("-c").arg(&cmd);
+            self.apply_env(&mut command)?;
+            return Ok((command, cmd));
+        }
+
+        let mut args = self
+            .program
+            .iter()
+            .chain(self.argv.iter())
+            .map(|arg| expand(arg, false))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let command_line = args
+            .iter()
+            .map(|arg| format!("{:?}", arg))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        // Without an explicit program the first argv entry is the executable
+        let program = args.remove(0);
+        let mut command = Command::new(program);
+        command.args(args);
+        self.apply_env(&mut command)?;
+        Ok((command, command_line))
+    }
+}
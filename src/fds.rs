@@ -0,0 +1,213 @@
+// Copyright (C) 2025, Rafael Caricio <rafael@caricio.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License, v2.0.
+// If a copy of the MPL was not distributed with this file, You can obtain one at
+// <https://mozilla.org/MPL/2.0/>.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Extra pipes inherited by the subprocess as file descriptors 3, 4, … next to its standard
+//! streams.
+//!
+//! Each pipe has a role: `metadata` pipes carry one JSON line per frame to the subprocess, and
+//! `messages` pipes carry lines from the subprocess back to the element. Other file descriptors,
+//! e.g. the ring buffer of `transport=shm`, are passed after the pipes.
+
+use std::fs::File;
+use std::io;
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd};
+use std::os::unix::process::CommandExt;
+use std::process::Command;
+
+use crate::writer::{self, Waker, WriteError};
+
+/// File descriptor of the first extra pipe in the subprocess.
+pub const FIRST_FD: RawFd = 3;
+
+/// Placeholders of the extra pipes, expanded to their `/dev/fd/N` paths.
+pub const PLACEHOLDERS: &[&str] = &["fd3", "fd4", "fd5", "fd6", "fd7", "fd8", "fd9"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FdRole {
+    // Written by the element, one JSON line per frame
+    Metadata,
+    // Written by the subprocess, each line is posted as an element message
+    Messages,
+}
+
+impl FdRole {
+    /// Parses the entries of the `extra-fds` property, the first one being fd 3.
+    pub fn parse_list(entries: &[String]) -> Result<Vec<FdRole>, String> {
+        if entries.len() > PLACEHOLDERS.len() {
+            return Err(format!("At most {} extra file descriptors are supported", PLACEHOLDERS.len()));
+        }
+
+        let roles = entries
+            .iter()
+            .map(|entry| match entry.as_str() {
+                "metadata" => Ok(FdRole::Metadata),
+                "messages" => Ok(FdRole::Messages),
+                _ => Err(format!("Invalid extra fd {:?}, expected metadata or messages", entry)),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if roles.iter().filter(|role| **role == FdRole::Metadata).count() > 1 {
+            return Err("Only one extra fd can carry the metadata".to_string());
+        }
+
+        Ok(roles)
+    }
+}
+
+/// End of an extra pipe kept by the element.
+#[derive(Debug)]
+pub struct ExtraPipe {
+    /// File descriptor number in the subprocess
+    pub fd: RawFd,
+    pub role: FdRole,
+    pub file: File,
+}
+
+/// Extra pipes before the subprocess is spawned.
+#[derive(Debug)]
+pub struct ExtraFds {
+    pipes: Vec<ExtraPipe>,
+    // Duplicates passed to the subprocess with their number there, closed in the element once
+    // it is spawned
+    child_ends: Vec<(OwnedFd, RawFd)>,
+}
+
+impl ExtraFds {
+    pub fn new(roles: &[FdRole]) -> io::Result<Self> {
+        let mut pipes = Vec::with_capacity(roles.len());
+        let mut child_ends = Vec::with_capacity(roles.len());
+
+        for (fd, role) in (FIRST_FD..).zip(roles) {
+            let mut fds = [0; 2];
+            if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let (read, write) = unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) };
+
+            let (ours, theirs) = match role {
+                FdRole::Metadata => {
+                    // Non-blocking so that unlock() can interrupt the write
+                    writer::set_nonblocking(write.as_fd())?;
+                    (write, read)
+                }
+                FdRole::Messages => (read, write),
+            };
+
+            pipes.push(ExtraPipe {
+                fd,
+                role: *role,
+                file: File::from(ours),
+            });
+            child_ends.push((above_extra_fds(theirs)?, fd));
+        }
+
+        Ok(ExtraFds { pipes, child_ends })
+    }
+
+    /// Values of the `{fd3}`, `{fd4}`, … placeholders.
+    pub fn placeholders(&self) -> impl Iterator<Item = (&'static str, String)> + '_ {
+        PLACEHOLDERS
+            .iter()
+            .zip(&self.pipes)
+            .map(|(name, pipe)| (*name, format!("/dev/fd/{}", pipe.fd)))
+    }
+
+    /// Also passes `fd` to the subprocess, after the pipes. Returns its number in the subprocess.
+    pub fn push(&mut self, fd: BorrowedFd) -> io::Result<RawFd> {
+        let target = FIRST_FD + self.child_ends.len() as RawFd;
+        if target >= FIRST_FD + PLACEHOLDERS.len() as RawFd {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("At most {} extra file descriptors are supported", PLACEHOLDERS.len()),
+            ));
+        }
+
+        self.child_ends.push((above_extra_fds(fd.try_clone_to_owned()?)?, target));
+        Ok(target)
+    }
+
+    /// Makes `command` pass the pipes to the subprocess at their file descriptor numbers.
+    pub fn inherit(&self, command: &mut Command) {
+        let fds = self
+            .child_ends
+            .iter()
+            .map(|(end, fd)| (end.as_raw_fd(), *fd))
+            .collect::<Vec<_>>();
+
+        // Only dup2() runs between fork and exec. The duplicates don't have FD_CLOEXEC, and
+        // can't overwrite one of the sources which are all above the target numbers.
+        unsafe {
+            command.pre_exec(move || {
+                for &(src, dst) in &fds {
+                    if libc::dup2(src, dst) < 0 {
+                        return Err(io::Error::last_os_error());
+                    }
+                }
+                Ok(())
+            });
+        }
+    }
+
+    /// Closes the ends of the subprocess once it was spawned, so that reads see EOF when it
+    /// exits, and returns the ends of the element.
+    pub fn into_pipes(self) -> Vec<ExtraPipe> {
+        self.pipes
+    }
+}
+
+// Moves `fd` above the range of extra fds, so that the dup2() calls can't clobber each other
+fn above_extra_fds(fd: OwnedFd) -> io::Result<OwnedFd> {
+    let min = FIRST_FD + PLACEHOLDERS.len() as RawFd;
+    if fd.as_raw_fd() >= min {
+        return Ok(fd);
+    }
+
+    let dup = unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_DUPFD_CLOEXEC, min) };
+    if dup < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(unsafe { OwnedFd::from_raw_fd(dup) })
+}
+
+/// Writes a JSON line with the timestamps of each frame to a `metadata` pipe.
+#[derive(Debug)]
+pub struct MetadataWriter {
+    file: File,
+    // Index of the next frame written to the subprocess
+    seq: u64,
+}
+
+impl MetadataWriter {
+    pub fn new(file: File) -> Self {
+        MetadataWriter { file, seq: 0 }
+    }
+
+    pub fn write(&mut self, buffer: &gst::BufferRef, waker: &Waker) -> Result<(), WriteError> {
+        let line = metadata_line(self.seq, buffer);
+        writer::write_all(&mut self.file, line.as_bytes(), waker, None)?;
+        self.seq += 1;
+        Ok(())
+    }
+}
+
+fn metadata_line(seq: u64, buffer: &gst::BufferRef) -> String {
+    fn json<T: ToString>(value: Option<T>) -> String {
+        value.map_or_else(|| "null".to_string(), |value| value.to_string())
+    }
+
+    let offset = Some(buffer.offset()).filter(|offset| *offset != gst::ffi::GST_BUFFER_OFFSET_NONE);
+    format!(
+        "{{\"seq\":{},\"pts\":{},\"dts\":{},\"duration\":{},\"offset\":{},\"keyframe\":{}}}\n",
+        seq,
+        json(buffer.pts().map(gst::ClockTime::nseconds)),
+        json(buffer.dts().map(gst::ClockTime::nseconds)),
+        json(buffer.duration().map(gst::ClockTime::nseconds)),
+        json(offset),
+        !buffer.flags().contains(gst::BufferFlags::DELTA_UNIT),
+    )
+}
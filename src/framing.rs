@@ -0,0 +1,304 @@
+// Copyright (C) 2025, Rafael Caricio <rafael@caricio.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License, v2.0.
+// If a copy of the MPL was not distributed with this file, You can obtain one at
+// <https://mozilla.org/MPL/2.0/>.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Framing of the buffers written to, or read from, the subprocess.
+
+use gst::glib;
+
+use crate::length_prefixed::{self, Header};
+
+#[derive(Debug, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Clone, Copy, glib::Enum)]
+#[repr(u32)]
+#[enum_type(name = "GstSubprocessPipeFraming")]
+pub enum Framing {
+    #[default]
+    #[enum_value(name = "Raw: Buffers are written as they are", nick = "raw")]
+    Raw,
+    #[enum_value(name = "Y4M: YUV4MPEG2 stream with FRAME headers", nick = "y4m")]
+    Y4m,
+    #[enum_value(
+        name = "Length-prefixed: Binary header with size and timestamps before each buffer",
+        nick = "length-prefixed"
+    )]
+    LengthPrefixed,
+    #[enum_value(name = "WAV: RIFF WAVE header before the raw audio samples", nick = "wav")]
+    Wav,
+}
+
+pub const Y4M_FRAME_HEADER: &[u8] = b"FRAME\n";
+
+impl Framing {
+    /// Header written once at the start of the stream, before the first frame.
+    ///
+    /// Fails if the caps can't be expressed with this framing.
+    pub fn stream_header(self, caps: &gst::CapsRef, caps_seq: u32) -> Result<Option<Vec<u8>>, String> {
+        match self {
+            Framing::Raw => Ok(None),
+            Framing::Y4m => {
+                let info = gst_video::VideoInfo::from_caps(caps)
+                    .map_err(|_| format!("Y4M framing requires raw video caps, got {}", caps))?;
+                y4m_header(&info).map(|header| Some(header.into_bytes()))
+            }
+            Framing::LengthPrefixed => Ok(Some(caps_record(caps, caps_seq))),
+            Framing::Wav => {
+                let info = gst_audio::AudioInfo::from_caps(caps)
+                    .map_err(|_| format!("WAV framing requires raw audio caps, got {}", caps))?;
+                wav_header(&info).map(Some)
+            }
+        }
+    }
+
+    /// Header written before the next frame when the caps change while the subprocess keeps
+    /// running, if the framing can signal caps changes.
+    pub fn caps_change_header(self, caps: &gst::CapsRef, caps_seq: u32) -> Option<Vec<u8>> {
+        match self {
+            Framing::Raw | Framing::Y4m | Framing::Wav => None,
+            Framing::LengthPrefixed => Some(caps_record(caps, caps_seq)),
+        }
+    }
+
+    /// Wraps `buffer` with the per-frame header and the pending stream header, if any.
+    ///
+    /// The frame memory is shared with `buffer`, nothing is copied.
+    pub fn frame(self, buffer: &gst::Buffer, caps_seq: u32, stream_header: Option<&[u8]>) -> gst::Buffer {
+        let mut prefix = stream_header.map(<[u8]>::to_vec).unwrap_or_default();
+        match self {
+            Framing::Raw | Framing::Wav => (),
+            Framing::Y4m => prefix.extend_from_slice(Y4M_FRAME_HEADER),
+            Framing::LengthPrefixed => {
+                prefix.extend_from_slice(&Header::for_buffer(buffer, caps_seq).to_bytes())
+            }
+        }
+
+        if prefix.is_empty() {
+            return buffer.clone();
+        }
+
+        let mut framed = gst::Buffer::from_mut_slice(prefix);
+        framed.append(buffer.clone());
+        // Keep the timestamps for the writer thread, appending only keeps the prefix' metadata
+        buffer
+            .copy_into(
+                framed.get_mut().unwrap(),
+                gst::BufferCopyFlags::FLAGS | gst::BufferCopyFlags::TIMESTAMPS,
+                ..,
+            )
+            .expect("Failed to copy buffer metadata");
+        framed
+    }
+}
+
+/// Slot record for `buffer`, copied to `slot` of the shared memory ring buffer, after the pending
+/// stream header if any.
+pub fn slot_record(buffer: &gst::BufferRef, slot: u32, caps_seq: u32, stream_header: Option<&[u8]>) -> gst::Buffer {
+    let mut record = stream_header.map(<[u8]>::to_vec).unwrap_or_default();
+    record.extend_from_slice(&Header::for_slot(buffer, caps_seq).to_bytes());
+    record.extend_from_slice(&length_prefixed::slot_payload(slot, buffer.size() as u64));
+    gst::Buffer::from_mut_slice(record)
+}
+
+// Caps record of the length-prefixed framing, with the serialized caps as payload
+fn caps_record(caps: &gst::CapsRef, caps_seq: u32) -> Vec<u8> {
+    let caps = caps.to_string();
+    let mut record = Header::for_caps(caps.len(), caps_seq).to_bytes().to_vec();
+    record.extend_from_slice(caps.as_bytes());
+    record
+}
+
+// Y4M colorspace tag for the raw video formats it can express
+fn y4m_colorspace(format: gst_video::VideoFormat) -> Option<&'static str> {
+    use gst_video::VideoFormat;
+
+    let colorspace = match format {
+        VideoFormat::I420 => "420jpeg",
+        VideoFormat::Y41b => "411",
+        VideoFormat::Y42b => "422",
+        VideoFormat::Y444 => "444",
+        VideoFormat::Gray8 => "mono",
+        VideoFormat::Gray16Le => "mono16",
+        VideoFormat::I42010le => "420p10",
+        VideoFormat::I42210le => "422p10",
+        VideoFormat::Y44410le => "444p10",
+        VideoFormat::I42012le => "420p12",
+        VideoFormat::I42212le => "422p12",
+        VideoFormat::Y44412le => "444p12",
+        _ => return None,
+    };
+
+    Some(colorspace)
+}
+
+// Raw video format of a Y4M colorspace tag, including the aliases of 420jpeg
+fn y4m_format(colorspace: &str) -> Option<gst_video::VideoFormat> {
+    use gst_video::VideoFormat;
+
+    let format = match colorspace {
+        "420jpeg" | "420mpeg2" | "420paldv" | "420" => VideoFormat::I420,
+        "411" => VideoFormat::Y41b,
+        "422" => VideoFormat::Y42b,
+        "444" => VideoFormat::Y444,
+        "mono" => VideoFormat::Gray8,
+        "mono16" => VideoFormat::Gray16Le,
+        "420p10" => VideoFormat::I42010le,
+        "422p10" => VideoFormat::I42210le,
+        "444p10" => VideoFormat::Y44410le,
+        "420p12" => VideoFormat::I42012le,
+        "422p12" => VideoFormat::I42212le,
+        "444p12" => VideoFormat::Y44412le,
+        _ => return None,
+    };
+
+    Some(format)
+}
+
+/// Builds the YUV4MPEG2 stream header for the negotiated video info.
+pub fn y4m_header(info: &gst_video::VideoInfo) -> Result<String, String> {
+    let colorspace = y4m_colorspace(info.format())
+        .ok_or_else(|| format!("Format {} can't be expressed in Y4M", info.format()))?;
+
+    // Mixed interlacing ("m") would need per-frame parameters in the FRAME headers, which are
+    // not written
+    let interlacing = match (info.interlace_mode(), info.field_order()) {
+        (gst_video::VideoInterlaceMode::Progressive, _) => "p",
+        (gst_video::VideoInterlaceMode::Interleaved, gst_video::VideoFieldOrder::TopFieldFirst) => "t",
+        (gst_video::VideoInterlaceMode::Interleaved, gst_video::VideoFieldOrder::BottomFieldFirst) => "b",
+        (gst_video::VideoInterlaceMode::Interleaved, _) => {
+            return Err("Interlaced video without field order can't be expressed in Y4M".to_string());
+        }
+        (mode, _) => return Err(format!("Interlace mode {:?} can't be expressed in Y4M", mode)),
+    };
+
+    // 0:0 marks an unknown frame rate
+    let fps = info.fps();
+    let (fps_n, fps_d) = if fps.numer() > 0 {
+        (fps.numer(), fps.denom())
+    } else {
+        (0, 0)
+    };
+    let par = info.par();
+
+    Ok(format!(
+        "YUV4MPEG2 W{} H{} F{}:{} I{} A{}:{} C{}\n",
+        info.width(),
+        info.height(),
+        fps_n,
+        fps_d,
+        interlacing,
+        par.numer(),
+        par.denom(),
+        colorspace
+    ))
+}
+
+/// Builds a RIFF WAVE header for a stream of unknown length.
+///
+/// The RIFF and data chunk sizes are set to their maximum, which readers of pipes accept as
+/// "until the end of the stream".
+pub fn wav_header(info: &gst_audio::AudioInfo) -> Result<Vec<u8>, String> {
+    let format = info.format_info();
+
+    // WAV samples are little endian, unsigned if 8 bit and signed otherwise
+    let format_tag: u16 = match (format.is_float(), format.is_signed(), format.width()) {
+        _ if format.width() != format.depth() => None,
+        _ if format.width() > 8 && !format.is_little_endian() => None,
+        (true, _, 32 | 64) => Some(3),
+        (false, false, 8) => Some(1),
+        (false, true, 16 | 24 | 32) => Some(1),
+        _ => None,
+    }
+    .ok_or_else(|| format!("Format {} can't be expressed in WAV", info.format()))?;
+
+    let channels = u16::try_from(info.channels())
+        .map_err(|_| format!("Too many channels for WAV: {}", info.channels()))?;
+    let block_align = u16::try_from(info.bpf())
+        .map_err(|_| format!("Too many channels for WAV: {}", info.channels()))?;
+
+    let mut header = Vec::with_capacity(44);
+    header.extend_from_slice(b"RIFF");
+    header.extend_from_slice(&u32::MAX.to_le_bytes());
+    header.extend_from_slice(b"WAVE");
+    header.extend_from_slice(b"fmt ");
+    header.extend_from_slice(&16u32.to_le_bytes());
+    header.extend_from_slice(&format_tag.to_le_bytes());
+    header.extend_from_slice(&channels.to_le_bytes());
+    header.extend_from_slice(&info.rate().to_le_bytes());
+    header.extend_from_slice(&info.rate().saturating_mul(block_align as u32).to_le_bytes());
+    header.extend_from_slice(&block_align.to_le_bytes());
+    header.extend_from_slice(&(format.width() as u16).to_le_bytes());
+    header.extend_from_slice(b"data");
+    header.extend_from_slice(&u32::MAX.to_le_bytes());
+
+    Ok(header)
+}
+
+/// Parses a YUV4MPEG2 stream header line into the video info of its frames.
+///
+/// Streams without a colorspace tag are 4:2:0, as the format specifies.
+pub fn parse_y4m_header(line: &str) -> Result<gst_video::VideoInfo, String> {
+    let mut tags = line.trim_end_matches('\n').split(' ');
+    if tags.next() != Some("YUV4MPEG2") {
+        return Err("Not a YUV4MPEG2 stream".to_string());
+    }
+
+    let ratio = |value: &str| -> Result<gst::Fraction, String> {
+        let (numer, denom) = value
+            .split_once(':')
+            .and_then(|(n, d)| Some((n.parse::<i32>().ok()?, d.parse::<i32>().ok()?)))
+            .ok_or_else(|| format!("Invalid Y4M ratio {}", value))?;
+        Ok(gst::Fraction::new(numer, denom))
+    };
+    let dimension = |value: &str| value.parse::<u32>().map_err(|_| format!("Invalid Y4M dimension {}", value));
+
+    let (mut width, mut height) = (None, None);
+    let mut fps = gst::Fraction::new(0, 1);
+    let mut par = gst::Fraction::new(1, 1);
+    let mut interlacing = "p";
+    let mut format = gst_video::VideoFormat::I420;
+
+    for tag in tags.filter(|tag| !tag.is_empty()) {
+        // Keys are single ASCII letters, a tag may start with any other character
+        let (key, value) = tag
+            .split_at_checked(1)
+            .ok_or_else(|| format!("Invalid Y4M tag {:?}", tag))?;
+        match key {
+            "W" => width = Some(dimension(value)?),
+            "H" => height = Some(dimension(value)?),
+            // 0:0 marks an unknown frame rate or pixel aspect ratio
+            "F" if value != "0:0" => fps = ratio(value)?,
+            "A" if value != "0:0" => par = ratio(value)?,
+            "I" => interlacing = value,
+            "C" => {
+                format = y4m_format(value).ok_or_else(|| format!("Unsupported Y4M colorspace {}", value))?;
+            }
+            // Frame rate and aspect ratio placeholders, and extensions (X)
+            _ => (),
+        }
+    }
+
+    let (Some(width), Some(height)) = (width, height) else {
+        return Err("Y4M header without frame size".to_string());
+    };
+
+    let (interlace_mode, field_order) = match interlacing {
+        "p" | "?" => (gst_video::VideoInterlaceMode::Progressive, None),
+        "t" => (gst_video::VideoInterlaceMode::Interleaved, Some(gst_video::VideoFieldOrder::TopFieldFirst)),
+        "b" => (gst_video::VideoInterlaceMode::Interleaved, Some(gst_video::VideoFieldOrder::BottomFieldFirst)),
+        "m" => (gst_video::VideoInterlaceMode::Mixed, None),
+        _ => return Err(format!("Invalid Y4M interlacing {}", interlacing)),
+    };
+
+    let mut builder = gst_video::VideoInfo::builder(format, width, height)
+        .fps(fps)
+        .par(par)
+        .interlace_mode(interlace_mode);
+    if let Some(field_order) = field_order {
+        builder = builder.field_order(field_order);
+    }
+
+    builder.build().map_err(|err| format!("Invalid Y4M header: {}", err))
+}
@@ -0,0 +1,262 @@
+// Copyright (C) 2025, Rafael Caricio <rafael@caricio.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License, v2.0.
+// If a copy of the MPL was not distributed with this file, You can obtain one at
+// <https://mozilla.org/MPL/2.0/>.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Length-prefixed stream format written with `framing=length-prefixed`, and a reader for it.
+//!
+//! The stream is a sequence of records, each a fixed size [`Header`] followed by `length` bytes
+//! of payload. All header fields are little endian:
+//!
+//! | Offset | Size | Field                                        |
+//! |--------|------|----------------------------------------------|
+//! | 0      | 4    | Magic, `GSPF`                                |
+//! | 4      | 2    | Version, currently 1                         |
+//! | 6      | 2    | Record kind, 0 = buffer, 1 = caps, 2 = slot  |
+//! | 8      | 8    | Payload length                               |
+//! | 16     | 8    | PTS in nanoseconds, `u64::MAX` if none       |
+//! | 24     | 8    | DTS in nanoseconds, `u64::MAX` if none       |
+//! | 32     | 8    | Duration in nanoseconds, `u64::MAX` if none  |
+//! | 40     | 4    | `GstBufferFlags`                             |
+//! | 44     | 4    | Caps sequence number                         |
+//!
+//! A caps record, with the caps serialized as a string in its payload, comes before the first
+//! buffer and after every caps change. Buffer records carry the sequence number of the caps
+//! record that describes them.
+//!
+//! With `transport=shm`, slot records take the place of buffer records. Their 16 byte payload
+//! is the index of the ring buffer slot holding the frame (4 bytes), 4 reserved bytes and the
+//! size of the frame (8 bytes), see [`crate::shm`].
+//!
+//! ```no_run
+//! use gstsubprocesspipe::length_prefixed::{Reader, RecordKind};
+//!
+//! for record in Reader::new(std::io::stdin().lock()) {
+//!     let record = record.unwrap();
+//!     match record.header.kind {
+//!         RecordKind::Caps => println!("caps: {}", record.caps().unwrap()),
+//!         RecordKind::Buffer => println!("buffer: {} bytes at {:?}", record.payload.len(), record.header.pts),
+//!         RecordKind::Slot => println!("slot and size: {:?}", record.slot().unwrap()),
+//!     }
+//! }
+//! ```
+
+use std::io::{self, Read};
+
+pub const MAGIC: [u8; 4] = *b"GSPF";
+pub const VERSION: u16 = 1;
+pub const HEADER_SIZE: usize = 48;
+pub const SLOT_PAYLOAD_SIZE: usize = 16;
+/// Largest payload accepted by the [`Reader`], enough for an 8K frame with 16 bit RGBA.
+pub const MAX_RECORD_SIZE: u64 = 1 << 30;
+
+// Payload memory allocated up front, larger payloads grow as they are read
+const PAYLOAD_PREALLOC: usize = 64 << 20;
+
+const NONE: u64 = u64::MAX;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordKind {
+    Buffer,
+    Caps,
+    Slot,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Header {
+    pub kind: RecordKind,
+    pub length: u64,
+    pub pts: Option<gst::ClockTime>,
+    pub dts: Option<gst::ClockTime>,
+    pub duration: Option<gst::ClockTime>,
+    pub flags: gst::BufferFlags,
+    pub caps_seq: u32,
+}
+
+impl Header {
+    /// Header for a buffer record, with the buffer size as payload length.
+    pub fn for_buffer(buffer: &gst::BufferRef, caps_seq: u32) -> Self {
+        Header {
+            kind: RecordKind::Buffer,
+            length: buffer.size() as u64,
+            pts: buffer.pts(),
+            dts: buffer.dts(),
+            duration: buffer.duration(),
+            flags: buffer.flags(),
+            caps_seq,
+        }
+    }
+
+    /// Header for a slot record describing `buffer`, which was copied to a ring buffer slot.
+    pub fn for_slot(buffer: &gst::BufferRef, caps_seq: u32) -> Self {
+        Header {
+            kind: RecordKind::Slot,
+            length: SLOT_PAYLOAD_SIZE as u64,
+            ..Header::for_buffer(buffer, caps_seq)
+        }
+    }
+
+    /// Header for a caps record with a payload of `length` bytes.
+    pub fn for_caps(length: usize, caps_seq: u32) -> Self {
+        Header {
+            kind: RecordKind::Caps,
+            length: length as u64,
+            pts: None,
+            dts: None,
+            duration: None,
+            flags: gst::BufferFlags::empty(),
+            caps_seq,
+        }
+    }
+
+    pub fn to_bytes(&self) -> [u8; HEADER_SIZE] {
+        let kind: u16 = match self.kind {
+            RecordKind::Buffer => 0,
+            RecordKind::Caps => 1,
+            RecordKind::Slot => 2,
+        };
+
+        let mut bytes = [0u8; HEADER_SIZE];
+        bytes[0..4].copy_from_slice(&MAGIC);
+        bytes[4..6].copy_from_slice(&VERSION.to_le_bytes());
+        bytes[6..8].copy_from_slice(&kind.to_le_bytes());
+        bytes[8..16].copy_from_slice(&self.length.to_le_bytes());
+        bytes[16..24].copy_from_slice(&self.pts.map_or(NONE, |t| t.nseconds()).to_le_bytes());
+        bytes[24..32].copy_from_slice(&self.dts.map_or(NONE, |t| t.nseconds()).to_le_bytes());
+        bytes[32..40].copy_from_slice(&self.duration.map_or(NONE, |t| t.nseconds()).to_le_bytes());
+        bytes[40..44].copy_from_slice(&self.flags.bits().to_le_bytes());
+        bytes[44..48].copy_from_slice(&self.caps_seq.to_le_bytes());
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8; HEADER_SIZE]) -> io::Result<Self> {
+        let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+        let u16_at = |pos: usize| u16::from_le_bytes(bytes[pos..pos + 2].try_into().unwrap());
+        let u32_at = |pos: usize| u32::from_le_bytes(bytes[pos..pos + 4].try_into().unwrap());
+        let u64_at = |pos: usize| u64::from_le_bytes(bytes[pos..pos + 8].try_into().unwrap());
+        let time_at = |pos: usize| Some(u64_at(pos)).filter(|t| *t != NONE).map(gst::ClockTime::from_nseconds);
+
+        if bytes[0..4] != MAGIC {
+            return Err(invalid(format!("Invalid magic {:?}", &bytes[0..4])));
+        }
+
+        let version = u16_at(4);
+        if version != VERSION {
+            return Err(invalid(format!("Unsupported version {}", version)));
+        }
+
+        let kind = match u16_at(6) {
+            0 => RecordKind::Buffer,
+            1 => RecordKind::Caps,
+            2 => RecordKind::Slot,
+            kind => return Err(invalid(format!("Unknown record kind {}", kind))),
+        };
+
+        Ok(Header {
+            kind,
+            length: u64_at(8),
+            pts: time_at(16),
+            dts: time_at(24),
+            duration: time_at(32),
+            flags: gst::BufferFlags::from_bits_truncate(u32_at(40)),
+            caps_seq: u32_at(44),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    pub header: Header,
+    pub payload: Vec<u8>,
+}
+
+impl Record {
+    /// The serialized caps of a caps record.
+    pub fn caps(&self) -> Option<&str> {
+        match self.header.kind {
+            RecordKind::Caps => std::str::from_utf8(&self.payload).ok(),
+            RecordKind::Buffer | RecordKind::Slot => None,
+        }
+    }
+
+    /// The slot index and frame size of a slot record.
+    pub fn slot(&self) -> Option<(u32, u64)> {
+        if self.header.kind != RecordKind::Slot || self.payload.len() != SLOT_PAYLOAD_SIZE {
+            return None;
+        }
+
+        let slot = u32::from_le_bytes(self.payload[0..4].try_into().unwrap());
+        let size = u64::from_le_bytes(self.payload[8..16].try_into().unwrap());
+        Some((slot, size))
+    }
+}
+
+/// Payload of a slot record.
+pub fn slot_payload(slot: u32, size: u64) -> [u8; SLOT_PAYLOAD_SIZE] {
+    let mut payload = [0u8; SLOT_PAYLOAD_SIZE];
+    payload[0..4].copy_from_slice(&slot.to_le_bytes());
+    payload[8..16].copy_from_slice(&size.to_le_bytes());
+    payload
+}
+
+/// Reads records from a length-prefixed stream.
+#[derive(Debug)]
+pub struct Reader<R> {
+    inner: R,
+}
+
+impl<R: Read> Reader<R> {
+    pub fn new(inner: R) -> Self {
+        Reader { inner }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Reads the next record. Returns `None` at the end of the stream.
+    pub fn read_record(&mut self) -> io::Result<Option<Record>> {
+        let mut bytes = [0u8; HEADER_SIZE];
+
+        // Only an end of stream between records is clean
+        let mut filled = 0;
+        while filled < HEADER_SIZE {
+            match self.inner.read(&mut bytes[filled..]) {
+                Ok(0) if filled == 0 => return Ok(None),
+                Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+                Ok(n) => filled += n,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => (),
+                Err(err) => return Err(err),
+            }
+        }
+
+        let header = Header::from_bytes(&bytes)?;
+        if header.length > MAX_RECORD_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Record of {} bytes exceeds the maximum of {} bytes", header.length, MAX_RECORD_SIZE),
+            ));
+        }
+
+        // Only allocate what is actually read, a corrupt length fails with a short read
+        let length = header.length as usize;
+        let mut payload = Vec::with_capacity(length.min(PAYLOAD_PREALLOC));
+        self.inner.by_ref().take(header.length).read_to_end(&mut payload)?;
+        if payload.len() < length {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+
+        Ok(Some(Record { header, payload }))
+    }
+}
+
+impl<R: Read> Iterator for Reader<R> {
+    type Item = io::Result<Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_record().transpose()
+    }
+}
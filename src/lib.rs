@@ -1,14 +1,36 @@
-mod videopipesink;
+// vmsplice(), F_SETPIPE_SZ, memfd_create() and abstract Unix sockets have no portable fallback
+#[cfg(not(target_os = "linux"))]
+compile_error!("gst-subprocess-pipe only supports Linux");
+
+mod audio;
+mod command;
+mod fds;
+mod framing;
+pub mod length_prefixed;
+mod pack;
+mod pipefilter;
+mod pipesink;
+mod pipesrc;
+mod queue;
+pub mod shm;
+mod splice;
+mod template;
+mod transport;
+mod writer;
 
 use gst::glib;
 
 // Used for testing to directly register the element without requiring the plugin loading
 pub fn register_element() -> Result<(), glib::BoolError> {
-    videopipesink::register_element()
+    pipesink::register_element()?;
+    pipefilter::register_element()?;
+    pipesrc::register_element()
 }
 
 fn plugin_init(plugin: &gst::Plugin) -> Result<(), glib::BoolError> {
-    videopipesink::register(plugin)
+    pipesink::register(plugin)?;
+    pipefilter::register(plugin)?;
+    pipesrc::register(plugin)
 }
 
 gst::plugin_define!(
@@ -0,0 +1,137 @@
+// Copyright (C) 2025, Rafael Caricio <rafael@caricio.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License, v2.0.
+// If a copy of the MPL was not distributed with this file, You can obtain one at
+// <https://mozilla.org/MPL/2.0/>.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Removal and restoration of row padding in video frames.
+//!
+//! Upstream elements may produce frames with padded strides or planes at arbitrary offsets,
+//! described by a `GstVideoMeta`. Consumers reading raw frames expect the planes one after the
+//! other, with rows of exactly the width of the image, and producers write them that way.
+
+use gst::glib;
+use gst_video::prelude::*;
+
+// Size in bytes of the rows and the number of rows of each plane when tightly packed, or None
+// for formats without a well defined packed layout, e.g. tiled or palettized formats
+fn packed_layout(info: &gst_video::VideoInfo) -> Option<Vec<(usize, usize)>> {
+    let format_info = info.format_info();
+    let flags = format_info.flags();
+
+    if format_info.is_tiled()
+        || flags.contains(gst_video::VideoFormatFlags::PALETTE)
+        || flags.contains(gst_video::VideoFormatFlags::COMPLEX)
+    {
+        return None;
+    }
+
+    let mut layout = vec![(0, 0); format_info.n_planes() as usize];
+
+    for comp in 0..format_info.n_components() as u8 {
+        let pstride = format_info.pixel_stride()[comp as usize];
+        if pstride <= 0 {
+            return None;
+        }
+
+        // Components interleaved in the same plane share its rows
+        let (row_size, rows) = &mut layout[format_info.plane()[comp as usize] as usize];
+        *row_size = (*row_size).max(info.comp_width(comp) as usize * pstride as usize);
+        *rows = (*rows).max(info.comp_height(comp) as usize);
+    }
+
+    Some(layout)
+}
+
+/// Returns `buffer` with its planes tightly packed, without row padding or gaps between planes.
+///
+/// Buffers that are already tightly packed, and formats that can't be packed, are returned as
+/// they are without copying.
+pub fn pack_planes(buffer: &gst::Buffer, info: &gst_video::VideoInfo) -> Result<gst::Buffer, glib::BoolError> {
+    let Some(layout) = packed_layout(info) else {
+        return Ok(buffer.clone());
+    };
+
+    // Mapping the frame applies the strides and offsets of the video meta, if any
+    let frame = gst_video::VideoFrameRef::from_buffer_ref_readable(buffer.as_ref(), info)?;
+    let strides = frame.info().stride();
+    let offsets = frame.info().offset();
+
+    let mut packed_size = 0;
+    let mut is_packed = true;
+    for (plane, &(row_size, rows)) in layout.iter().enumerate() {
+        is_packed &= strides[plane] as usize == row_size && offsets[plane] == packed_size;
+        packed_size += row_size * rows;
+    }
+
+    if is_packed && buffer.size() == packed_size {
+        return Ok(buffer.clone());
+    }
+
+    let mut data = Vec::with_capacity(packed_size);
+    for (plane, &(row_size, rows)) in layout.iter().enumerate() {
+        let plane_data = frame.plane_data(plane as u32)?;
+        let stride = usize::try_from(strides[plane])
+            .map_err(|_| glib::bool_error!("Negative stride {} not supported", strides[plane]))?;
+
+        for row in 0..rows {
+            let start = row * stride;
+            let row_data = plane_data
+                .get(start..start + row_size)
+                .ok_or_else(|| glib::bool_error!("Plane {} is smaller than the video info", plane))?;
+            data.extend_from_slice(row_data);
+        }
+    }
+
+    let mut packed = gst::Buffer::from_mut_slice(data);
+    buffer.copy_into(
+        packed.get_mut().unwrap(),
+        gst::BufferCopyFlags::FLAGS | gst::BufferCopyFlags::TIMESTAMPS,
+        ..,
+    )?;
+
+    Ok(packed)
+}
+
+/// Size in bytes of a tightly packed frame.
+pub fn packed_size(info: &gst_video::VideoInfo) -> usize {
+    match packed_layout(info) {
+        Some(layout) => layout.iter().map(|(row_size, rows)| row_size * rows).sum(),
+        None => info.size(),
+    }
+}
+
+/// Returns a buffer with the planes of a tightly packed frame at the strides and offsets of
+/// `info`, the layout downstream elements expect without a `GstVideoMeta`.
+///
+/// Frames that don't have the packed size, e.g. because they are already laid out as in
+/// `info`, are returned as they are.
+pub fn unpack_planes(data: Vec<u8>, info: &gst_video::VideoInfo) -> Result<gst::Buffer, glib::BoolError> {
+    let layout = match packed_layout(info) {
+        Some(layout) if data.len() == packed_size(info) && data.len() != info.size() => layout,
+        _ => return Ok(gst::Buffer::from_mut_slice(data)),
+    };
+
+    let mut buffer = gst::Buffer::with_size(info.size())?;
+    {
+        let buffer = buffer.get_mut().unwrap();
+        let mut frame = gst_video::VideoFrameRef::from_buffer_ref_writable(buffer, info)?;
+        let strides = info.stride();
+
+        let mut packed = data.as_slice();
+        for (plane, &(row_size, rows)) in layout.iter().enumerate() {
+            let stride = strides[plane] as usize;
+            let plane_data = frame.plane_data_mut(plane as u32)?;
+
+            for row in 0..rows {
+                let (row_data, rest) = packed.split_at(row_size);
+                plane_data[row * stride..row * stride + row_size].copy_from_slice(row_data);
+                packed = rest;
+            }
+        }
+    }
+
+    Ok(buffer)
+}
@@ -0,0 +1,1030 @@
+// Copyright (C) 2025, Rafael Caricio <rafael@caricio.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License, v2.0.
+// If a copy of the MPL was not distributed with this file, You can obtain one at
+// <https://mozilla.org/MPL/2.0/>.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+use gst::glib;
+use gst::prelude::*;
+use gst::subclass::prelude::*;
+use once_cell::sync::Lazy;
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader};
+use std::os::fd::AsFd;
+use std::process::{Child, ChildStdin, ChildStdout, Stdio};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+
+use crate::audio;
+use crate::command::{terminate, CommandSettings};
+use crate::framing::Framing;
+use crate::pack;
+use crate::pipesink::StopSignal;
+use crate::template::Variables;
+use crate::writer::{self, read_up_to, InterruptibleReader, Waker, WriteError};
+
+static CAT: Lazy<gst::DebugCategory> = Lazy::new(|| {
+    gst::DebugCategory::new(
+        "pipefilter",
+        gst::DebugColorFlags::empty(),
+        Some("Subprocess Pipe Filter Element"),
+    )
+});
+
+static WAIT_FOR_EXIT_DEFAULT: gst::ClockTime = gst::ClockTime::from_mseconds(100);
+static KILL_TIMEOUT_DEFAULT: gst::ClockTime = gst::ClockTime::from_seconds(5);
+
+// A buffer written to the subprocess whose output was not read back yet
+#[derive(Debug, Clone)]
+struct Frame {
+    // Empty buffer with the timestamps, offsets and flags of the input buffer
+    meta: gst::Buffer,
+    // Size of the output frame
+    size: usize,
+    // Set for raw video output
+    video_info: Option<gst_video::VideoInfo>,
+}
+
+// Frame written to the subprocess, or serialized event that must not overtake its output
+#[derive(Debug, Clone)]
+enum Item {
+    Frame(Frame),
+    Event(gst::Event),
+}
+
+// Frames and events between the chain function and the output loop
+#[derive(Debug)]
+struct InFlight {
+    items: VecDeque<Item>,
+    // Stdin was closed on EOS, EOS is forwarded once all frames were read back
+    eos: bool,
+    flushing: bool,
+    // Result of the last push of the output loop, returned by the next chain call
+    flow: Result<gst::FlowSuccess, gst::FlowError>,
+}
+
+impl Default for InFlight {
+    fn default() -> Self {
+        InFlight {
+            items: VecDeque::new(),
+            eos: false,
+            flushing: false,
+            flow: Ok(gst::FlowSuccess::Ok),
+        }
+    }
+}
+
+impl InFlight {
+    fn frames(&self) -> usize {
+        self.items.iter().filter(|item| matches!(item, Item::Frame(..))).count()
+    }
+}
+
+// Plugin state
+#[derive(Default)]
+struct State {
+    child_process: Option<Child>,
+    stdin: Option<ChildStdin>,
+    stderr_thread: Option<thread::JoinHandle<()>>,
+    caps: Option<gst::Caps>,
+    // Set for raw video input
+    video_info: Option<gst_video::VideoInfo>,
+    // Set for raw audio input
+    audio_info: Option<gst_audio::AudioInfo>,
+    // Set for raw video output
+    output_video_info: Option<gst_video::VideoInfo>,
+    // Set for raw audio output
+    output_audio_info: Option<gst_audio::AudioInfo>,
+    // Stream header not yet written to the current subprocess
+    stream_header: Option<Vec<u8>>,
+    // Incremented on every caps change, identifies the caps in length-prefixed headers
+    caps_seq: u32,
+}
+
+// Properties
+#[derive(Debug, Clone)]
+struct Settings {
+    command: CommandSettings,
+    output_caps: Option<gst::Caps>,
+    framing: Framing,
+    max_in_flight: u32,
+    wait_for_exit: gst::ClockTime,
+    stop_signal: StopSignal,
+    kill_timeout: gst::ClockTime,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            command: CommandSettings::default(),
+            output_caps: None,
+            framing: Framing::default(),
+            max_in_flight: 1,
+            wait_for_exit: WAIT_FOR_EXIT_DEFAULT,
+            stop_signal: StopSignal::default(),
+            kill_timeout: KILL_TIMEOUT_DEFAULT,
+        }
+    }
+}
+
+type StdoutReader = BufReader<InterruptibleReader<ChildStdout>>;
+
+pub struct PipeFilter {
+    sinkpad: gst::Pad,
+    srcpad: gst::Pad,
+    settings: Mutex<Settings>,
+    state: Mutex<State>,
+    in_flight: Mutex<InFlight>,
+    in_flight_cond: Condvar,
+    // Only used by the output loop, outside of the state which is locked while writing
+    stdout: Mutex<Option<StdoutReader>>,
+    // Duration of the last input buffer, for latency queries that must not wait for a write
+    frame_duration: Mutex<Option<gst::ClockTime>>,
+    // Interrupts blocked writes to stdin and reads from stdout
+    waker: Arc<Waker>,
+}
+
+#[glib::object_subclass]
+impl ObjectSubclass for PipeFilter {
+    const NAME: &'static str = "PipeFilter";
+    type Type = super::PipeFilter;
+    type ParentType = gst::Element;
+
+    fn with_class(klass: &Self::Class) -> Self {
+        let templ = klass.pad_template("sink").unwrap();
+        let sinkpad = gst::Pad::builder_from_template(&templ)
+            .chain_function(|pad, parent, buffer| {
+                PipeFilter::catch_panic_pad_function(
+                    parent,
+                    || Err(gst::FlowError::Error),
+                    |filter| filter.sink_chain(pad, buffer),
+                )
+            })
+            .event_function(|pad, parent, event| {
+                PipeFilter::catch_panic_pad_function(
+                    parent,
+                    || false,
+                    |filter| filter.sink_event(pad, event),
+                )
+            })
+            .query_function(|pad, parent, query| {
+                PipeFilter::catch_panic_pad_function(
+                    parent,
+                    || false,
+                    |filter| filter.sink_query(pad, query),
+                )
+            })
+            .build();
+
+        let templ = klass.pad_template("src").unwrap();
+        let srcpad = gst::Pad::builder_from_template(&templ)
+            .activatemode_function(|pad, parent, mode, active| {
+                PipeFilter::catch_panic_pad_function(
+                    parent,
+                    || Err(gst::loggable_error!(CAT, "Panic activating src pad")),
+                    |filter| filter.src_activatemode(pad, mode, active),
+                )
+            })
+            .query_function(|pad, parent, query| {
+                PipeFilter::catch_panic_pad_function(
+                    parent,
+                    || false,
+                    |filter| filter.src_query(pad, query),
+                )
+            })
+            .build();
+
+        Self {
+            sinkpad,
+            srcpad,
+            settings: Mutex::new(Settings::default()),
+            state: Mutex::new(State::default()),
+            in_flight: Mutex::new(InFlight::default()),
+            in_flight_cond: Condvar::new(),
+            stdout: Mutex::new(None),
+            frame_duration: Mutex::new(None),
+            waker: Arc::new(Waker::new().expect("Failed to create wakeup pipe")),
+        }
+    }
+}
+
+impl ObjectImpl for PipeFilter {
+    fn properties() -> &'static [glib::ParamSpec] {
+        static PROPERTIES: Lazy<Vec<glib::ParamSpec>> = Lazy::new(|| {
+            let mut properties = CommandSettings::properties();
+            properties.extend([
+                glib::ParamSpecBoxed::builder::<gst::Caps>("output-caps")
+                    .nick("Output caps")
+                    .blurb("Caps of the frames read back from the subprocess, fields not set are taken from the input caps")
+                    .mutable_ready()
+                    .build(),
+                glib::ParamSpecEnum::builder_with_default("framing", Framing::default())
+                    .nick("Framing")
+                    .blurb("How buffers are written to the subprocess, its output is always read as raw frames")
+                    .mutable_ready()
+                    .build(),
+                glib::ParamSpecUInt::builder("max-in-flight")
+                    .nick("Max in flight")
+                    .blurb("Maximum number of frames written to the subprocess before their output was read back")
+                    .minimum(1)
+                    .default_value(1)
+                    .mutable_ready()
+                    .build(),
+                glib::ParamSpecUInt64::builder("wait-for-exit")
+                    .nick("Wait for exit")
+                    .blurb("Wait time in nanoseconds for the subprocess to exit after the stdin pipe is closed")
+                    .default_value(WAIT_FOR_EXIT_DEFAULT.nseconds())
+                    .mutable_playing()
+                    .build(),
+                glib::ParamSpecEnum::builder_with_default("stop-signal", StopSignal::default())
+                    .nick("Stop signal")
+                    .blurb("Signal sent to the subprocess if it doesn't exit within wait-for-exit")
+                    .mutable_playing()
+                    .build(),
+                glib::ParamSpecUInt64::builder("kill-timeout")
+                    .nick("Kill timeout")
+                    .blurb("Wait time in nanoseconds after the stop signal before sending SIGKILL")
+                    .default_value(KILL_TIMEOUT_DEFAULT.nseconds())
+                    .mutable_playing()
+                    .build(),
+            ]);
+            properties
+        });
+
+        PROPERTIES.as_ref()
+    }
+
+    fn set_property(&self, _id: usize, value: &glib::Value, pspec: &glib::ParamSpec) {
+        let mut settings = self.settings.lock().unwrap();
+        if settings.command.set_property(pspec.name(), value) {
+            return;
+        }
+
+        match pspec.name() {
+            "output-caps" => {
+                settings.output_caps = value.get().expect("type checked upstream");
+            }
+            "framing" => {
+                settings.framing = value.get().expect("type checked upstream");
+            }
+            "max-in-flight" => {
+                settings.max_in_flight = value.get().expect("type checked upstream");
+            }
+            "wait-for-exit" => {
+                settings.wait_for_exit = value.get().expect("type checked upstream");
+            }
+            "stop-signal" => {
+                settings.stop_signal = value.get().expect("type checked upstream");
+            }
+            "kill-timeout" => {
+                settings.kill_timeout = value.get().expect("type checked upstream");
+            }
+            _ => unimplemented!(),
+        }
+    }
+
+    fn property(&self, _id: usize, pspec: &glib::ParamSpec) -> glib::Value {
+        let settings = self.settings.lock().unwrap();
+        if let Some(value) = settings.command.property(pspec.name()) {
+            return value;
+        }
+
+        match pspec.name() {
+            "output-caps" => {
+                settings.output_caps.to_value()
+            }
+            "framing" => {
+                settings.framing.to_value()
+            }
+            "max-in-flight" => {
+                settings.max_in_flight.to_value()
+            }
+            "wait-for-exit" => {
+                settings.wait_for_exit.to_value()
+            }
+            "stop-signal" => {
+                settings.stop_signal.to_value()
+            }
+            "kill-timeout" => {
+                settings.kill_timeout.to_value()
+            }
+            _ => unimplemented!(),
+        }
+    }
+
+    fn constructed(&self) {
+        self.parent_constructed();
+
+        let obj = self.obj();
+        obj.add_pad(&self.sinkpad).unwrap();
+        obj.add_pad(&self.srcpad).unwrap();
+    }
+}
+
+impl GstObjectImpl for PipeFilter {}
+
+impl ElementImpl for PipeFilter {
+    fn metadata() -> Option<&'static gst::subclass::ElementMetadata> {
+        static ELEMENT_METADATA: Lazy<gst::subclass::ElementMetadata> = Lazy::new(|| {
+            gst::subclass::ElementMetadata::new(
+                "Pipe Filter",
+                "Filter/Video/Audio",
+                "Sends raw frames through a provided subprocess and outputs what it writes back",
+                "Rafael Caricio <rafael@caricio.com>",
+            )
+        });
+
+        Some(&*ELEMENT_METADATA)
+    }
+
+    fn pad_templates() -> &'static [gst::PadTemplate] {
+        static PAD_TEMPLATES: Lazy<Vec<gst::PadTemplate>> = Lazy::new(|| {
+            let mut caps = gst_video::VideoCapsBuilder::new().build();
+            caps.make_mut().append(audio::caps());
+
+            let sink_pad_template = gst::PadTemplate::new(
+                "sink",
+                gst::PadDirection::Sink,
+                gst::PadPresence::Always,
+                &caps,
+            )
+            .unwrap();
+
+            let src_pad_template = gst::PadTemplate::new(
+                "src",
+                gst::PadDirection::Src,
+                gst::PadPresence::Always,
+                &caps,
+            )
+            .unwrap();
+
+            vec![sink_pad_template, src_pad_template]
+        });
+
+        PAD_TEMPLATES.as_ref()
+    }
+
+    fn change_state(&self, transition: gst::StateChange) -> Result<gst::StateChangeSuccess, gst::StateChangeError> {
+        if transition == gst::StateChange::ReadyToPaused {
+            let settings = self.settings.lock().unwrap();
+            if let Err(err) = settings.command.validate().and_then(|_| settings.command.working_directory()) {
+                drop(settings);
+                self.post_error_message(err);
+                return Err(gst::StateChangeError);
+            }
+        }
+
+        let success = self.parent_change_state(transition)?;
+
+        if transition == gst::StateChange::PausedToReady {
+            let settings = self.settings.lock().unwrap().clone();
+            let mut state = self.state.lock().unwrap();
+            self.stop_child(&mut state, &settings);
+            *state = State::default();
+            *self.frame_duration.lock().unwrap() = None;
+        }
+
+        Ok(success)
+    }
+}
+
+// Caps of the frames read back: the output-caps property, completed with the fields of the
+// input caps it doesn't set
+fn output_caps(caps: &gst::CapsRef, configured: Option<&gst::Caps>) -> Result<gst::Caps, String> {
+    let Some(configured) = configured else {
+        return Ok(caps.to_owned());
+    };
+
+    let input = caps.structure(0).ok_or("Empty input caps")?;
+    let mut output = configured
+        .structure(0)
+        .ok_or("Empty output caps")?
+        .to_owned();
+
+    if output.name() == input.name() {
+        for (field, value) in input.iter() {
+            if !output.has_field(field) {
+                output.set_value(field, value.clone());
+            }
+        }
+    }
+
+    let output = gst::Caps::builder_full().structure(output).build();
+    if !output.is_fixed() {
+        return Err(format!("Output caps {} are not fixed", output));
+    }
+
+    Ok(output)
+}
+
+impl PipeFilter {
+    // Spawn the subprocess for the current input caps, with its stdout read by the output loop
+    fn spawn_child(&self, state: &mut State, settings: &Settings) -> Result<(), gst::ErrorMessage> {
+        let caps = state.caps.clone().unwrap();
+        let vars = Variables::from_caps(&caps).map_err(|err| {
+            gst::error_msg!(
+                gst::CoreError::Negotiation,
+                ["Failed to parse caps {}: {}", caps, err]
+            )
+        })?;
+
+        let stream_header = settings.framing.stream_header(&caps, state.caps_seq).map_err(|err| {
+            gst::error_msg!(gst::CoreError::Negotiation, ["{}", err])
+        })?;
+
+        let (mut command, command_line) = settings.command.command(&vars)?;
+        let current_dir = settings.command.working_directory()?;
+
+        gst::info!(CAT, imp = self, "Starting subprocess with command: {}", command_line);
+
+        let mut child = command
+            .current_dir(current_dir)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| {
+                gst::error_msg!(
+                    gst::ResourceError::Failed,
+                    ["Failed to start process: {}", e]
+                )
+            })?;
+
+        let pid = child.id();
+
+        let stdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+        let reader = writer::set_nonblocking(stdin.as_fd())
+            .and_then(|_| InterruptibleReader::new(stdout, self.waker.clone()));
+        let reader = match reader {
+            Ok(reader) => reader,
+            Err(err) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(gst::error_msg!(
+                    gst::ResourceError::Failed,
+                    ["Failed to make pipes non-blocking: {}", err]
+                ));
+            }
+        };
+
+        // Setup stderr monitoring
+        let stderr = child.stderr.take().unwrap();
+
+        let stderr_thread = thread::spawn({
+            let this = self.downgrade();
+            move || {
+                let reader = BufReader::new(stderr);
+                for line in reader.lines().map_while(Result::ok) {
+                    let this = match this.upgrade() {
+                        Some(this) => this,
+                        None => return,
+                    };
+                    gst::warning!(CAT, imp = this, "stderr: {}", line);
+                }
+            }
+        });
+
+        *self.stdout.lock().unwrap() = Some(BufReader::new(reader));
+        state.child_process = Some(child);
+        state.stdin = Some(stdin);
+        state.stderr_thread = Some(stderr_thread);
+        state.stream_header = stream_header;
+
+        gst::info!(CAT, imp = self, "Started subprocess with PID: {}", pid);
+        Ok(())
+    }
+
+    // Close the pipes, reap the subprocess and join the stderr monitoring thread.
+    //
+    // The output loop must not be reading stdout, i.e. no frames are in flight or the src pad
+    // task is paused.
+    fn stop_child(&self, state: &mut State, settings: &Settings) {
+        drop(state.stdin.take());
+        drop(self.stdout.lock().unwrap().take());
+
+        if let Some(mut child) = state.child_process.take() {
+            let pid = child.id();
+
+            match terminate(&mut child, settings.wait_for_exit, settings.stop_signal.as_raw(), settings.kill_timeout) {
+                Ok(status) => {
+                    gst::info!(CAT, imp = self, "Process (PID: {}) exited with {}", pid, status);
+                }
+                Err(err) => {
+                    gst::warning!(CAT, imp = self, "Failed to stop child process (PID: {}): {}", pid, err);
+                }
+            }
+        }
+
+        if let Some(thread) = state.stderr_thread.take() {
+            thread.join().unwrap();
+        }
+    }
+
+    // Wait until the output of all frames in flight was read back
+    fn drain(&self) -> Result<(), gst::FlowError> {
+        let mut in_flight = self.in_flight.lock().unwrap();
+        while in_flight.frames() > 0 && !in_flight.flushing {
+            in_flight.flow?;
+            in_flight = self.in_flight_cond.wait(in_flight).unwrap();
+        }
+
+        if in_flight.flushing {
+            return Err(gst::FlowError::Flushing);
+        }
+        in_flight.flow.map(|_| ())
+    }
+
+    fn set_caps(&self, caps: &gst::Caps) -> bool {
+        gst::debug!(CAT, imp = self, "Caps set to: {}", caps);
+
+        let settings = self.settings.lock().unwrap().clone();
+
+        if let Err(err) = settings.framing.stream_header(caps, 0) {
+            gst::element_imp_error!(self, gst::CoreError::Negotiation, ["{}", err]);
+            return false;
+        }
+
+        let output_caps = match output_caps(caps, settings.output_caps.as_ref()) {
+            Ok(output_caps) => output_caps,
+            Err(err) => {
+                gst::element_imp_error!(self, gst::CoreError::Negotiation, ["{}", err]);
+                return false;
+            }
+        };
+        let output_video_info = gst_video::VideoInfo::from_caps(&output_caps).ok();
+        let output_audio_info = gst_audio::AudioInfo::from_caps(&output_caps).ok();
+        // Audio buffers keep their number of samples, which only raw audio input defines
+        let input_audio = caps.structure(0).is_some_and(|s| s.name() == "audio/x-raw");
+        if output_video_info.is_none() && !(output_audio_info.is_some() && input_audio) {
+            gst::element_imp_error!(
+                self,
+                gst::CoreError::Negotiation,
+                ["Output caps {} not supported for input caps {}", output_caps, caps]
+            );
+            return false;
+        }
+
+        let mut state = self.state.lock().unwrap();
+        if state.caps.as_ref() == Some(caps) {
+            return true;
+        }
+
+        // The new subprocess gets the new caps, after the old one produced its last frames
+        if state.child_process.is_some() {
+            gst::info!(CAT, imp = self, "Caps changed, restarting subprocess");
+            if self.drain().is_err() {
+                return false;
+            }
+            self.stop_child(&mut state, &settings);
+        }
+
+        state.caps_seq = state.caps_seq.wrapping_add(1);
+        state.caps = Some(caps.clone());
+        state.video_info = gst_video::VideoInfo::from_caps(caps).ok();
+        state.audio_info = gst_audio::AudioInfo::from_caps(caps).ok();
+        state.output_video_info = output_video_info;
+        state.output_audio_info = output_audio_info;
+
+        if let Err(err) = self.spawn_child(&mut state, &settings) {
+            self.post_error_message(err);
+            return false;
+        }
+        drop(state);
+
+        self.queue_event(gst::event::Caps::new(&output_caps))
+    }
+
+    // Queue a serialized event behind the frames in flight, the output loop forwards it after
+    // their output
+    fn queue_event(&self, event: gst::Event) -> bool {
+        let mut in_flight = self.in_flight.lock().unwrap();
+        if in_flight.flushing {
+            return false;
+        }
+
+        in_flight.items.push_back(Item::Event(event));
+        self.in_flight_cond.notify_all();
+        true
+    }
+
+    // Added latency: the subprocess may hold back all frames in flight but the last one
+    fn latency(&self) -> gst::ClockTime {
+        let max_in_flight = self.settings.lock().unwrap().max_in_flight;
+        let frame_duration = self.frame_duration.lock().unwrap().unwrap_or(gst::ClockTime::ZERO);
+        frame_duration * u64::from(max_in_flight - 1)
+    }
+
+    fn sink_chain(&self, _pad: &gst::Pad, buffer: gst::Buffer) -> Result<gst::FlowSuccess, gst::FlowError> {
+        let settings = self.settings.lock().unwrap().clone();
+        let mut state = self.state.lock().unwrap();
+
+        if state.caps.is_none() {
+            gst::error!(CAT, imp = self, "No caps before the first buffer");
+            return Err(gst::FlowError::NotNegotiated);
+        }
+
+        // Restarted on the first buffer after a flush
+        if state.child_process.is_none() {
+            if let Err(err) = self.spawn_child(&mut state, &settings) {
+                self.post_error_message(err);
+                return Err(gst::FlowError::Error);
+            }
+        }
+
+        // The latency is only known once the first frame came in
+        if let Some(duration) = buffer.duration() {
+            let previous = self.frame_duration.lock().unwrap().replace(duration);
+            if previous.is_none() && settings.max_in_flight > 1 {
+                let _ = self.obj().post_message(gst::message::Latency::builder().src(&*self.obj()).build());
+            }
+        }
+
+        let converted;
+        let buffer = match (&state.video_info, &state.audio_info) {
+            (Some(info), _) => {
+                converted = pack::pack_planes(&buffer, info).map_err(|err| {
+                    gst::error!(CAT, imp = self, "Failed to pack video frame: {}", err);
+                    gst::FlowError::Error
+                })?;
+                &converted
+            }
+            (_, Some(info)) if info.layout() == gst_audio::AudioLayout::NonInterleaved => {
+                converted = audio::interleave(&buffer, info).map_err(|err| {
+                    gst::error!(CAT, imp = self, "Failed to interleave audio samples: {}", err);
+                    gst::FlowError::Error
+                })?;
+                &converted
+            }
+            _ => &buffer,
+        };
+
+        // Raw video frames keep their size, audio buffers their number of samples
+        let size = match (&state.output_video_info, &state.output_audio_info, &state.audio_info) {
+            (Some(info), _, _) => pack::packed_size(info),
+            (_, Some(output), Some(input)) => buffer.size() / input.bpf() as usize * output.bpf() as usize,
+            _ => unreachable!(),
+        };
+
+        let mut meta = gst::Buffer::new();
+        buffer
+            .copy_into(
+                meta.get_mut().unwrap(),
+                gst::BufferCopyFlags::FLAGS | gst::BufferCopyFlags::TIMESTAMPS,
+                ..,
+            )
+            .map_err(|_| gst::FlowError::Error)?;
+        let frame = Frame {
+            meta,
+            size,
+            video_info: state.output_video_info.clone(),
+        };
+
+        // Queued before writing, so that the output loop reads the output while the subprocess
+        // is still reading the input
+        {
+            let mut in_flight = self.in_flight.lock().unwrap();
+            while in_flight.frames() >= settings.max_in_flight as usize && !in_flight.flushing {
+                in_flight.flow?;
+                in_flight = self.in_flight_cond.wait(in_flight).unwrap();
+            }
+
+            if in_flight.flushing {
+                return Err(gst::FlowError::Flushing);
+            }
+            in_flight.flow?;
+            in_flight.items.push_back(Item::Frame(frame));
+            self.in_flight_cond.notify_all();
+        }
+
+        let framed = settings.framing.frame(buffer, state.caps_seq, state.stream_header.as_deref());
+        let pid = state.child_process.as_ref().unwrap().id();
+        let stdin = state.stdin.as_mut().unwrap();
+
+        match writer::write_buffer(stdin, &framed, 0, &self.waker, None) {
+            Ok(()) => {
+                state.stream_header = None;
+                gst::trace!(CAT, imp = self, "Wrote buffer of size {}", framed.size());
+                Ok(gst::FlowSuccess::Ok)
+            }
+            Err(WriteError::Flushing) => {
+                gst::debug!(CAT, imp = self, "Flushing, write interrupted");
+                Err(gst::FlowError::Flushing)
+            }
+            Err(err) => {
+                gst::element_imp_error!(
+                    self,
+                    gst::ResourceError::Write,
+                    ["Failed to write to process (PID: {}) stdin: {:?}", pid, err]
+                );
+                Err(gst::FlowError::Error)
+            }
+        }
+    }
+
+    fn sink_event(&self, pad: &gst::Pad, event: gst::Event) -> bool {
+        gst::log!(CAT, obj = pad, "Handling event {:?}", event);
+
+        match event.view() {
+            gst::EventView::Caps(caps) => {
+                let caps = caps.caps_owned();
+                self.set_caps(&caps)
+            }
+            gst::EventView::Eos(..) => {
+                // The output loop forwards EOS after the output of the last frame
+                if self.state.lock().unwrap().stdin.take().is_some() {
+                    gst::debug!(CAT, imp = self, "EOS, closing stdin");
+                }
+                let mut in_flight = self.in_flight.lock().unwrap();
+                in_flight.eos = true;
+                self.in_flight_cond.notify_all();
+                true
+            }
+            gst::EventView::FlushStart(..) => {
+                self.waker.wake();
+                {
+                    let mut in_flight = self.in_flight.lock().unwrap();
+                    in_flight.flushing = true;
+                    self.in_flight_cond.notify_all();
+                }
+
+                let ret = self.srcpad.push_event(event);
+                let _ = self.srcpad.pause_task();
+                ret
+            }
+            gst::EventView::FlushStop(..) => {
+                // Output of the flushed frames would come out after the flush, so the
+                // subprocess is restarted with the next buffer
+                let settings = self.settings.lock().unwrap().clone();
+                let mut state = self.state.lock().unwrap();
+                self.stop_child(&mut state, &settings);
+                drop(state);
+
+                // Like queue, keep the sticky events but segment and EOS, the data after the
+                // flush still needs them
+                {
+                    let mut in_flight = self.in_flight.lock().unwrap();
+                    let items = in_flight
+                        .items
+                        .drain(..)
+                        .filter(|item| {
+                            matches!(item, Item::Event(event) if event.is_sticky()
+                                && !matches!(event.type_(), gst::EventType::Segment | gst::EventType::Eos))
+                        })
+                        .collect();
+                    *in_flight = InFlight {
+                        items,
+                        ..InFlight::default()
+                    };
+                }
+                self.waker.reset();
+
+                let ret = self.srcpad.push_event(event);
+                self.start_task();
+                ret
+            }
+            // Pushed by the output loop, in order with the output of the frames before them
+            _ if event.is_serialized() => self.queue_event(event),
+            _ => gst::Pad::event_default(pad, Some(&*self.obj()), event),
+        }
+    }
+
+    fn sink_query(&self, pad: &gst::Pad, query: &mut gst::QueryRef) -> bool {
+        match query.view_mut() {
+            gst::QueryViewMut::Caps(q) => {
+                let template = pad.pad_template_caps();
+
+                // Without output caps, the input caps are the output caps
+                let caps = if self.settings.lock().unwrap().output_caps.is_some() {
+                    template
+                } else {
+                    let peer = self.srcpad.peer_query_caps(Some(&template));
+                    peer.intersect_with_mode(&template, gst::CapsIntersectMode::First)
+                };
+
+                let caps = match q.filter() {
+                    Some(filter) => filter.intersect_with_mode(&caps, gst::CapsIntersectMode::First),
+                    None => caps,
+                };
+
+                q.set_result(&caps);
+                true
+            }
+            // Downstream allocates for the output caps, not the frames written to the subprocess
+            gst::QueryViewMut::Allocation(..) => false,
+            _ => gst::Pad::query_default(pad, Some(&*self.obj()), query),
+        }
+    }
+
+    fn src_query(&self, pad: &gst::Pad, query: &mut gst::QueryRef) -> bool {
+        match query.view_mut() {
+            gst::QueryViewMut::Caps(q) => {
+                let template = pad.pad_template_caps();
+                let output_caps = self.settings.lock().unwrap().output_caps.clone();
+
+                let caps = match (pad.current_caps(), output_caps) {
+                    (Some(current), _) => current,
+                    (None, Some(output_caps)) => {
+                        output_caps.intersect_with_mode(&template, gst::CapsIntersectMode::First)
+                    }
+                    (None, None) => {
+                        let peer = self.sinkpad.peer_query_caps(Some(&template));
+                        peer.intersect_with_mode(&template, gst::CapsIntersectMode::First)
+                    }
+                };
+
+                let caps = match q.filter() {
+                    Some(filter) => filter.intersect_with_mode(&caps, gst::CapsIntersectMode::First),
+                    None => caps,
+                };
+
+                q.set_result(&caps);
+                true
+            }
+            gst::QueryViewMut::Latency(q) => {
+                if !self.sinkpad.peer_query(q.query_mut()) {
+                    return false;
+                }
+
+                let (live, min, max) = q.result();
+                let latency = self.latency();
+                gst::debug!(CAT, imp = self, "Adding latency {}", latency);
+                q.set(live, min + latency, max.map(|max| max + latency));
+                true
+            }
+            _ => gst::Pad::query_default(pad, Some(&*self.obj()), query),
+        }
+    }
+
+    fn src_activatemode(&self, _pad: &gst::Pad, mode: gst::PadMode, active: bool) -> Result<(), gst::LoggableError> {
+        if mode != gst::PadMode::Push {
+            return Err(gst::loggable_error!(CAT, "Only push mode is supported"));
+        }
+
+        if active {
+            *self.in_flight.lock().unwrap() = InFlight::default();
+            self.waker.reset();
+            self.start_task();
+        } else {
+            self.waker.wake();
+            {
+                let mut in_flight = self.in_flight.lock().unwrap();
+                in_flight.flushing = true;
+                self.in_flight_cond.notify_all();
+            }
+            self.srcpad
+                .stop_task()
+                .map_err(|err| gst::loggable_error!(CAT, "Failed to stop task: {}", err))?;
+        }
+
+        Ok(())
+    }
+
+    fn start_task(&self) {
+        let this = self.downgrade();
+        let res = self.srcpad.start_task(move || {
+            if let Some(this) = this.upgrade() {
+                this.output_loop();
+            }
+        });
+
+        if let Err(err) = res {
+            gst::error!(CAT, imp = self, "Failed to start task: {}", err);
+        }
+    }
+
+    // Stop the output loop with `flow` and make the chain function return it
+    fn pause(&self, flow: Result<gst::FlowSuccess, gst::FlowError>) {
+        let mut in_flight = self.in_flight.lock().unwrap();
+        if !in_flight.flushing {
+            in_flight.flow = flow;
+        }
+        self.in_flight_cond.notify_all();
+        drop(in_flight);
+
+        let _ = self.srcpad.pause_task();
+    }
+
+    // Body of the src pad task, reads the output of the oldest frame in flight and pushes it
+    // with the timestamps of the input frame
+    fn output_loop(&self) {
+        let frame = {
+            let mut in_flight = self.in_flight.lock().unwrap();
+            loop {
+                if in_flight.flushing {
+                    drop(in_flight);
+                    self.pause(Err(gst::FlowError::Flushing));
+                    return;
+                }
+
+                match in_flight.items.front() {
+                    Some(Item::Frame(frame)) => break frame.clone(),
+                    Some(Item::Event(..)) => {
+                        let Some(Item::Event(event)) = in_flight.items.pop_front() else {
+                            unreachable!()
+                        };
+                        self.in_flight_cond.notify_all();
+                        drop(in_flight);
+
+                        gst::log!(CAT, imp = self, "Forwarding event {:?}", event);
+                        self.srcpad.push_event(event);
+                        return;
+                    }
+                    None => (),
+                }
+
+                if in_flight.eos {
+                    in_flight.eos = false;
+                    drop(in_flight);
+                    gst::debug!(CAT, imp = self, "All frames read back, forwarding EOS");
+                    self.srcpad.push_event(gst::event::Eos::new());
+                    self.pause(Err(gst::FlowError::Eos));
+                    return;
+                }
+
+                in_flight = self.in_flight_cond.wait(in_flight).unwrap();
+            }
+        };
+
+        let data = {
+            let mut stdout = self.stdout.lock().unwrap();
+            let Some(reader) = stdout.as_mut() else {
+                drop(stdout);
+                self.pause(Err(gst::FlowError::Flushing));
+                return;
+            };
+            read_up_to(reader, frame.size)
+        };
+
+        let data = match data {
+            Ok(data) if data.len() == frame.size => data,
+            _ if self.waker.is_flushing() => {
+                gst::debug!(CAT, imp = self, "Flushing, read interrupted");
+                self.pause(Err(gst::FlowError::Flushing));
+                return;
+            }
+            Ok(data) => {
+                let frames = self.in_flight.lock().unwrap().frames();
+                gst::element_imp_error!(
+                    self,
+                    gst::ResourceError::Read,
+                    ("Subprocess closed its stdout with {} frames in flight", frames),
+                    ["Read {} of {} bytes of the next frame", data.len(), frame.size]
+                );
+                self.pause(Err(gst::FlowError::Error));
+                return;
+            }
+            Err(err) => {
+                gst::element_imp_error!(
+                    self,
+                    gst::ResourceError::Read,
+                    ["Failed to read from process stdout: {}", err]
+                );
+                self.pause(Err(gst::FlowError::Error));
+                return;
+            }
+        };
+
+        let buffer = match &frame.video_info {
+            Some(info) => pack::unpack_planes(data, info),
+            None => Ok(gst::Buffer::from_mut_slice(data)),
+        };
+        let mut buffer = match buffer {
+            Ok(buffer) => buffer,
+            Err(err) => {
+                gst::element_imp_error!(self, gst::StreamError::Failed, ["{}", err]);
+                self.pause(Err(gst::FlowError::Error));
+                return;
+            }
+        };
+        let _ = frame.meta.copy_into(
+            buffer.get_mut().unwrap(),
+            gst::BufferCopyFlags::FLAGS | gst::BufferCopyFlags::TIMESTAMPS,
+            ..,
+        );
+
+        {
+            let mut in_flight = self.in_flight.lock().unwrap();
+            in_flight.items.pop_front();
+            self.in_flight_cond.notify_all();
+        }
+
+        gst::trace!(CAT, imp = self, "Pushing buffer {:?}", buffer);
+        let flow = self.srcpad.push(buffer);
+
+        if let Err(err) = flow {
+            gst::debug!(CAT, imp = self, "Pausing after flow {:?}", err);
+            if !matches!(err, gst::FlowError::Flushing | gst::FlowError::Eos) {
+                gst::element_imp_error!(
+                    self,
+                    gst::StreamError::Failed,
+                    ["Streaming stopped, reason {:?}", err]
+                );
+                self.srcpad.push_event(gst::event::Eos::new());
+            }
+            self.pause(flow);
+        }
+    }
+}
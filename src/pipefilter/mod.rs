@@ -12,15 +12,15 @@ use gst::prelude::*;
 mod imp;
 
 glib::wrapper! {
-    pub struct VideoPipeSink(ObjectSubclass<imp::VideoPipeSink>) @extends gst_base::BaseSink, gst::Element, gst::Object;
+    pub struct PipeFilter(ObjectSubclass<imp::PipeFilter>) @extends gst::Element, gst::Object;
 }
 
 pub fn register(plugin: &gst::Plugin) -> Result<(), glib::BoolError> {
     gst::Element::register(
         Some(plugin),
-        "videopipesink",
+        "pipefilter",
         gst::Rank::NONE,
-        VideoPipeSink::static_type(),
+        PipeFilter::static_type(),
     )
 }
 
@@ -28,8 +28,8 @@ pub fn register(plugin: &gst::Plugin) -> Result<(), glib::BoolError> {
 pub fn register_element() -> Result<(), glib::BoolError> {
     gst::Element::register(
         None,
-        "videopipesink",
+        "pipefilter",
         gst::Rank::NONE,
-        VideoPipeSink::static_type(),
+        PipeFilter::static_type(),
     )
 }
@@ -0,0 +1,2073 @@
+// Copyright (C) 2025, Rafael Caricio <rafael@caricio.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License, v2.0.
+// If a copy of the MPL was not distributed with this file, You can obtain one at
+// <https://mozilla.org/MPL/2.0/>.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+use gst::glib;
+use gst::prelude::*;
+use gst::subclass::prelude::*;
+use gst_base::prelude::*;
+use gst_base::subclass::prelude::*;
+use once_cell::sync::Lazy;
+use std::fs::File;
+use std::os::fd::{AsFd, OwnedFd};
+use std::os::unix::process::ExitStatusExt;
+use std::path::Path;
+use std::process::{Child, ExitStatus, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::{CapsChange, PipeSinkImpl, RestartPolicy, StallAction, StopSignal};
+use crate::audio;
+use crate::command::{wait_interruptible, wait_timeout, CommandSettings};
+use crate::fds::{ExtraFds, FdRole, MetadataWriter};
+use crate::framing::{self, Framing};
+use crate::pack;
+use crate::queue::{FrameQueue, Leaky, Limits, PushError};
+use crate::shm::Ring;
+use crate::splice::{self, Splicer};
+use crate::template::Variables;
+use crate::transport::{self, Fifo, Input, Transport};
+use crate::writer::{self, Waker, WriteError};
+
+static CAT: Lazy<gst::DebugCategory> = Lazy::new(|| {
+    gst::DebugCategory::new(
+        "pipesink",
+        gst::DebugColorFlags::empty(),
+        Some("Subprocess Pipe Sink Elements"),
+    )
+});
+
+static WAIT_FOR_EXIT_DEFAULT: gst::ClockTime = gst::ClockTime::from_mseconds(100);
+static KILL_TIMEOUT_DEFAULT: gst::ClockTime = gst::ClockTime::from_seconds(5);
+static EOS_TIMEOUT_DEFAULT: gst::ClockTime = gst::ClockTime::from_seconds(10);
+static FIFO_OPEN_TIMEOUT_DEFAULT: gst::ClockTime = gst::ClockTime::from_seconds(10);
+static RESTART_BACKOFF_DEFAULT: gst::ClockTime = gst::ClockTime::from_seconds(1);
+static RECONNECT_BACKOFF_MIN: gst::ClockTime = gst::ClockTime::from_mseconds(10);
+static RECONNECT_BACKOFF_MAX: gst::ClockTime = gst::ClockTime::from_seconds(30);
+static SHM_SLOTS_DEFAULT: u32 = 4;
+
+// Plugin state
+struct State {
+    child_process: Option<Child>,
+    // Connected to the socket of transport=unix-socket, which has no subprocess
+    connected: bool,
+    // Where the frames are written to, stdin of the subprocess, a FIFO or a socket
+    input: Option<Input>,
+    fifo: Option<Fifo>,
+    // Ring buffer of transport=shm
+    ring: Option<Ring>,
+    // Extra pipe the frame metadata is written to, if any
+    metadata: Option<MetadataWriter>,
+    // Splices frames into the input pipe with vmsplice=true, holds the buffers it may reference
+    splicer: Option<Splicer>,
+    cmd: String,
+    caps: Option<gst::Caps>,
+    // Set for raw video caps
+    video_info: Option<gst_video::VideoInfo>,
+    // Set for raw audio caps
+    audio_info: Option<gst_audio::AudioInfo>,
+    restarts: u32,
+    // PID and exit code of a subprocess whose restart was interrupted by a flush, restarted with
+    // the next buffer
+    pending_restart: Option<(u32, i32)>,
+    // The subprocess was drained on EOS, data after it goes to a new one
+    drained: bool,
+    started_at: Option<Instant>,
+    // Stream header not yet written to the current subprocess
+    stream_header: Option<Vec<u8>>,
+    // Incremented on every caps change, identifies the caps in length-prefixed headers
+    caps_seq: u32,
+    // Hands back its splicer, if any, so that the buffers stay alive until the subprocess exited
+    writer_thread: Option<thread::JoinHandle<Option<Splicer>>>,
+    stdout_thread: Option<thread::JoinHandle<()>>,
+    stderr_thread: Option<thread::JoinHandle<()>>,
+    // Readers of the extra pipes with the messages role
+    message_threads: Vec<thread::JoinHandle<()>>,
+}
+
+impl State {
+    // Whether frames can be written, to a subprocess or a socket
+    fn is_running(&self) -> bool {
+        self.child_process.is_some() || self.connected
+    }
+}
+
+// Statistics exposed through the stats property
+#[derive(Debug, Default)]
+struct Stats {
+    buffers_written: u64,
+    bytes_written: u64,
+    buffers_dropped: u64,
+    write_latency_total: gst::ClockTime,
+    write_latency_max: gst::ClockTime,
+    restarts: u32,
+    pid: Option<u32>,
+}
+
+impl Stats {
+    fn record_write(&mut self, size: usize, latency: Duration) {
+        let latency = gst::ClockTime::try_from(latency).unwrap_or(gst::ClockTime::MAX);
+
+        self.buffers_written += 1;
+        self.bytes_written += size as u64;
+        self.write_latency_total = self.write_latency_total.saturating_add(latency);
+        self.write_latency_max = self.write_latency_max.max(latency);
+    }
+
+    fn to_structure(&self, name: &str) -> gst::Structure {
+        gst::Structure::builder(name)
+            .field("buffers-written", self.buffers_written)
+            .field("bytes-written", self.bytes_written)
+            .field("buffers-dropped", self.buffers_dropped)
+            .field("write-latency-total", self.write_latency_total)
+            .field("write-latency-max", self.write_latency_max)
+            .field("restarts", self.restarts)
+            .field("pid", self.pid.unwrap_or(0))
+            .build()
+    }
+}
+
+// Properties
+#[derive(Debug, Clone)]
+struct Settings {
+    command: CommandSettings,
+    wait_for_exit: gst::ClockTime,
+    stop_signal: StopSignal,
+    kill_timeout: gst::ClockTime,
+    eos_timeout: gst::ClockTime,
+    on_caps_change: CapsChange,
+    restart_policy: RestartPolicy,
+    max_restarts: i32,
+    restart_backoff: gst::ClockTime,
+    error_on_nonzero_exit: bool,
+    write_timeout: gst::ClockTime,
+    stall_action: StallAction,
+    max_queued_buffers: u32,
+    max_queued_bytes: u64,
+    leaky: Leaky,
+    framing: Framing,
+    pack_planes: bool,
+    caps: Option<gst::Caps>,
+    transport: Transport,
+    fifo_path: Option<String>,
+    fifo_open_timeout: gst::ClockTime,
+    extra_fds: Vec<String>,
+    socket_path: Option<String>,
+    shm_slots: u32,
+    shm_slot_size: u64,
+    vmsplice: bool,
+    pipe_size: u32,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings { 
+            command: CommandSettings::default(),
+            wait_for_exit: WAIT_FOR_EXIT_DEFAULT,
+            stop_signal: StopSignal::default(),
+            kill_timeout: KILL_TIMEOUT_DEFAULT,
+            eos_timeout: EOS_TIMEOUT_DEFAULT,
+            on_caps_change: CapsChange::default(),
+            restart_policy: RestartPolicy::default(),
+            max_restarts: -1,
+            restart_backoff: RESTART_BACKOFF_DEFAULT,
+            error_on_nonzero_exit: false,
+            write_timeout: gst::ClockTime::ZERO,
+            stall_action: StallAction::default(),
+            max_queued_buffers: 0,
+            max_queued_bytes: 0,
+            leaky: Leaky::default(),
+            framing: Framing::default(),
+            pack_planes: true,
+            caps: None,
+            transport: Transport::default(),
+            fifo_path: None,
+            fifo_open_timeout: FIFO_OPEN_TIMEOUT_DEFAULT,
+            extra_fds: Vec::new(),
+            socket_path: None,
+            shm_slots: SHM_SLOTS_DEFAULT,
+            shm_slot_size: 0,
+            vmsplice: false,
+            pipe_size: 0,
+         }
+    }
+}
+
+impl Settings {
+    fn queue_limits(&self) -> Limits {
+        Limits {
+            max_buffers: self.max_queued_buffers,
+            max_bytes: self.max_queued_bytes,
+            leaky: self.leaky,
+        }
+    }
+
+    // With transport=shm, stdin carries length-prefixed caps and slot records
+    fn framing(&self) -> Framing {
+        match self.transport {
+            Transport::Shm => Framing::LengthPrefixed,
+            _ => self.framing,
+        }
+    }
+
+}
+
+pub struct PipeSink {
+    settings: Mutex<Settings>,
+    state: Mutex<State>,
+    // Outside of the state so that unlock() doesn't wait for a blocked render()
+    waker: Waker,
+    queue: Mutex<Option<Arc<FrameQueue>>>,
+    // Separate from the state so that it can be queried while render() is blocked
+    stats: Mutex<Stats>,
+}
+
+impl Default for PipeSink {
+    fn default() -> Self {
+        Self {
+            settings: Mutex::new(Settings::default()),
+            state: Mutex::new(State {
+                child_process: None,
+                connected: false,
+                input: None,
+                fifo: None,
+                ring: None,
+                metadata: None,
+                splicer: None,
+                cmd: String::new(),
+                caps: None,
+                video_info: None,
+                audio_info: None,
+                restarts: 0,
+                pending_restart: None,
+                drained: false,
+                started_at: None,
+                stream_header: None,
+                caps_seq: 0,
+                writer_thread: None,
+                stdout_thread: None,
+                stderr_thread: None,
+                message_threads: Vec::new(),
+            }),
+            waker: Waker::new().expect("Failed to create wakeup pipe"),
+            queue: Mutex::new(None),
+            stats: Mutex::new(Stats::default()),
+        }
+    }
+}
+
+#[glib::object_subclass]
+impl ObjectSubclass for PipeSink {
+    const NAME: &'static str = "PipeSink";
+    const ABSTRACT: bool = true;
+    type Type = super::PipeSink;
+    type ParentType = gst_base::BaseSink;
+}
+
+impl ObjectImpl for PipeSink {
+    fn properties() -> &'static [glib::ParamSpec] {
+        static PROPERTIES: Lazy<Vec<glib::ParamSpec>> = Lazy::new(|| {
+            let mut properties = CommandSettings::properties();
+            properties.extend([
+                glib::ParamSpecUInt64::builder("wait-for-exit")
+                    .nick("Wait for exit")
+                    .blurb("Wait time in nanoseconds for the subprocess to exit after the stdin pipe is closed")
+                    .default_value(0)
+                    .mutable_playing()
+                    .build(),
+                glib::ParamSpecEnum::builder_with_default("stop-signal", StopSignal::default())
+                    .nick("Stop signal")
+                    .blurb("Signal sent to the subprocess if it doesn't exit within wait-for-exit")
+                    .mutable_playing()
+                    .build(),
+                glib::ParamSpecUInt64::builder("kill-timeout")
+                    .nick("Kill timeout")
+                    .blurb("Wait time in nanoseconds after the stop signal before sending SIGKILL")
+                    .default_value(KILL_TIMEOUT_DEFAULT.nseconds())
+                    .mutable_playing()
+                    .build(),
+                glib::ParamSpecUInt64::builder("eos-timeout")
+                    .nick("EOS timeout")
+                    .blurb("Wait time in nanoseconds for the subprocess to exit on EOS before posting EOS")
+                    .default_value(EOS_TIMEOUT_DEFAULT.nseconds())
+                    .mutable_playing()
+                    .build(),
+                glib::ParamSpecEnum::builder_with_default("on-caps-change", CapsChange::default())
+                    .nick("On caps change")
+                    .blurb("What to do with the running subprocess when the caps change")
+                    .mutable_ready()
+                    .build(),
+                glib::ParamSpecEnum::builder_with_default("restart-policy", RestartPolicy::default())
+                    .nick("Restart policy")
+                    .blurb("Whether to restart the subprocess when it exits while streaming")
+                    .mutable_ready()
+                    .build(),
+                glib::ParamSpecInt::builder("max-restarts")
+                    .nick("Max restarts")
+                    .blurb("Maximum number of subprocess restarts (-1 = unlimited)")
+                    .minimum(-1)
+                    .default_value(-1)
+                    .mutable_ready()
+                    .build(),
+                glib::ParamSpecUInt64::builder("restart-backoff")
+                    .nick("Restart backoff")
+                    .blurb("Time in nanoseconds to wait before restarting the subprocess")
+                    .default_value(RESTART_BACKOFF_DEFAULT.nseconds())
+                    .mutable_ready()
+                    .build(),
+                glib::ParamSpecBoolean::builder("error-on-nonzero-exit")
+                    .nick("Error on non-zero exit")
+                    .blurb("Post an error if the subprocess exits with a non-zero code when stopping")
+                    .default_value(false)
+                    .mutable_ready()
+                    .build(),
+                glib::ParamSpecUInt64::builder("write-timeout")
+                    .nick("Write timeout")
+                    .blurb("Time in nanoseconds a buffer may take to be written before the subprocess is considered stalled (0 = disabled)")
+                    .default_value(0)
+                    .mutable_playing()
+                    .build(),
+                glib::ParamSpecEnum::builder_with_default("stall-action", StallAction::default())
+                    .nick("Stall action")
+                    .blurb("What to do when writing a buffer takes longer than write-timeout")
+                    .mutable_playing()
+                    .build(),
+                glib::ParamSpecUInt::builder("max-queued-buffers")
+                    .nick("Max queued buffers")
+                    .blurb("Maximum number of buffers queued for a writer thread (0 = unlimited, writes are synchronous if both limits are 0)")
+                    .default_value(0)
+                    .mutable_ready()
+                    .build(),
+                glib::ParamSpecUInt64::builder("max-queued-bytes")
+                    .nick("Max queued bytes")
+                    .blurb("Maximum number of bytes queued for a writer thread (0 = unlimited, writes are synchronous if both limits are 0)")
+                    .default_value(0)
+                    .mutable_ready()
+                    .build(),
+                glib::ParamSpecEnum::builder_with_default("leaky", Leaky::default())
+                    .nick("Leaky")
+                    .blurb("Where to drop buffers when the writer queue is full")
+                    .mutable_ready()
+                    .build(),
+                glib::ParamSpecEnum::builder_with_default("framing", Framing::default())
+                    .nick("Framing")
+                    .blurb("How buffers are framed when written to the subprocess")
+                    .mutable_ready()
+                    .build(),
+                glib::ParamSpecBoolean::builder("pack-planes")
+                    .nick("Pack planes")
+                    .blurb("Remove row padding from raw video frames and write their planes tightly packed")
+                    .default_value(true)
+                    .mutable_playing()
+                    .build(),
+                glib::ParamSpecBoxed::builder::<gst::Caps>("caps")
+                    .nick("Caps")
+                    .blurb("Restrict the caps accepted by the sink, e.g. video/x-raw,format=I420")
+                    .mutable_ready()
+                    .build(),
+                glib::ParamSpecEnum::builder_with_default("transport", Transport::default())
+                    .nick("Transport")
+                    .blurb("How buffers are passed to the subprocess")
+                    .mutable_ready()
+                    .build(),
+                glib::ParamSpecString::builder("fifo-path")
+                    .nick("FIFO path")
+                    .blurb("Path of the named pipe for transport=fifo, defaults to a new temporary directory")
+                    .mutable_ready()
+                    .build(),
+                glib::ParamSpecUInt64::builder("fifo-open-timeout")
+                    .nick("FIFO open timeout")
+                    .blurb("Wait time in nanoseconds for the subprocess to open the FIFO with transport=fifo")
+                    .default_value(FIFO_OPEN_TIMEOUT_DEFAULT.nseconds())
+                    .mutable_ready()
+                    .build(),
+                gst::ParamSpecArray::builder("extra-fds")
+                    .nick("Extra file descriptors")
+                    .blurb("Extra pipes passed to the subprocess as fd 3, 4, …: metadata (one JSON line per frame) or messages (lines posted as element messages)")
+                    .element_spec(&glib::ParamSpecString::builder("role").build())
+                    .mutable_ready()
+                    .build(),
+                glib::ParamSpecString::builder("socket-path")
+                    .nick("Socket path")
+                    .blurb("Unix domain socket to connect to for transport=unix-socket, in the abstract namespace if it starts with @")
+                    .mutable_ready()
+                    .build(),
+                glib::ParamSpecUInt::builder("shm-slots")
+                    .nick("Shared memory slots")
+                    .blurb("Number of frames in the ring buffer of transport=shm")
+                    .minimum(1)
+                    .maximum(1024)
+                    .default_value(SHM_SLOTS_DEFAULT)
+                    .mutable_ready()
+                    .build(),
+                glib::ParamSpecUInt64::builder("shm-slot-size")
+                    .nick("Shared memory slot size")
+                    .blurb("Size in bytes of the ring buffer slots of transport=shm, 0 for the frame size of the raw video caps")
+                    .default_value(0)
+                    .mutable_ready()
+                    .build(),
+                glib::ParamSpecBoolean::builder("vmsplice")
+                    .nick("vmsplice")
+                    .blurb("Hand the frame memory to the input pipe with vmsplice() instead of copying it, falls back to write() where not possible")
+                    .default_value(false)
+                    .mutable_ready()
+                    .build(),
+                glib::ParamSpecUInt::builder("pipe-size")
+                    .nick("Pipe size")
+                    .blurb("Size in bytes to resize the input pipe to with F_SETPIPE_SZ, 0 to keep the system default")
+                    .default_value(0)
+                    .mutable_ready()
+                    .build(),
+                glib::ParamSpecBoxed::builder::<gst::Structure>("stats")
+                    .nick("Statistics")
+                    .blurb("Statistics about the buffers written to the subprocess")
+                    .read_only()
+                    .build(),
+            ]);
+            properties
+        });
+
+        PROPERTIES.as_ref()
+    }
+
+    fn set_property(&self, _id: usize, value: &glib::Value, pspec: &glib::ParamSpec) {
+        let mut settings = self.settings.lock().unwrap();
+        if settings.command.set_property(pspec.name(), value) {
+            return;
+        }
+
+        match pspec.name() {
+            "wait-for-exit" => {
+                settings.wait_for_exit = value.get().expect("type checked upstream");
+            }
+            "stop-signal" => {
+                settings.stop_signal = value.get().expect("type checked upstream");
+            }
+            "kill-timeout" => {
+                settings.kill_timeout = value.get().expect("type checked upstream");
+            }
+            "eos-timeout" => {
+                settings.eos_timeout = value.get().expect("type checked upstream");
+            }
+            "on-caps-change" => {
+                settings.on_caps_change = value.get().expect("type checked upstream");
+            }
+            "restart-policy" => {
+                settings.restart_policy = value.get().expect("type checked upstream");
+            }
+            "max-restarts" => {
+                settings.max_restarts = value.get().expect("type checked upstream");
+            }
+            "restart-backoff" => {
+                settings.restart_backoff = value.get().expect("type checked upstream");
+            }
+            "error-on-nonzero-exit" => {
+                settings.error_on_nonzero_exit = value.get().expect("type checked upstream");
+            }
+            "write-timeout" => {
+                settings.write_timeout = value.get().expect("type checked upstream");
+            }
+            "stall-action" => {
+                settings.stall_action = value.get().expect("type checked upstream");
+            }
+            "max-queued-buffers" => {
+                settings.max_queued_buffers = value.get().expect("type checked upstream");
+            }
+            "max-queued-bytes" => {
+                settings.max_queued_bytes = value.get().expect("type checked upstream");
+            }
+            "leaky" => {
+                settings.leaky = value.get().expect("type checked upstream");
+            }
+            "framing" => {
+                settings.framing = value.get().expect("type checked upstream");
+            }
+            "pack-planes" => {
+                settings.pack_planes = value.get().expect("type checked upstream");
+            }
+            "caps" => {
+                settings.caps = value.get().expect("type checked upstream");
+            }
+            "transport" => {
+                settings.transport = value.get().expect("type checked upstream");
+            }
+            "fifo-path" => {
+                settings.fifo_path = value
+                    .get::<Option<String>>()
+                    .expect("type checked upstream")
+                    .filter(|path| !path.is_empty());
+            }
+            "fifo-open-timeout" => {
+                settings.fifo_open_timeout = value.get().expect("type checked upstream");
+            }
+            "extra-fds" => {
+                settings.extra_fds = value
+                    .get::<gst::Array>()
+                    .expect("type checked upstream")
+                    .iter()
+                    .map(|role| role.get::<String>().expect("type checked upstream"))
+                    .collect();
+            }
+            "shm-slots" => {
+                settings.shm_slots = value.get().expect("type checked upstream");
+            }
+            "shm-slot-size" => {
+                settings.shm_slot_size = value.get().expect("type checked upstream");
+            }
+            "vmsplice" => {
+                settings.vmsplice = value.get().expect("type checked upstream");
+            }
+            "pipe-size" => {
+                settings.pipe_size = value.get().expect("type checked upstream");
+            }
+            "socket-path" => {
+                settings.socket_path = value
+                    .get::<Option<String>>()
+                    .expect("type checked upstream")
+                    .filter(|path| !path.is_empty());
+            }
+            _ => unimplemented!(),
+        }
+    }
+
+    fn property(&self, _id: usize, pspec: &glib::ParamSpec) -> glib::Value {
+        if pspec.name() == "stats" {
+            let factory = self.obj().factory().map(|factory| factory.name()).unwrap_or_default();
+            let name = format!("application/x-{}-stats", factory);
+            return self.stats.lock().unwrap().to_structure(&name).to_value();
+        }
+
+        let settings = self.settings.lock().unwrap();
+        if let Some(value) = settings.command.property(pspec.name()) {
+            return value;
+        }
+
+        match pspec.name() {
+            "wait-for-exit" => {
+                settings.wait_for_exit.to_value()
+            }
+            "stop-signal" => {
+                settings.stop_signal.to_value()
+            }
+            "kill-timeout" => {
+                settings.kill_timeout.to_value()
+            }
+            "eos-timeout" => {
+                settings.eos_timeout.to_value()
+            }
+            "on-caps-change" => {
+                settings.on_caps_change.to_value()
+            }
+            "restart-policy" => {
+                settings.restart_policy.to_value()
+            }
+            "max-restarts" => {
+                settings.max_restarts.to_value()
+            }
+            "restart-backoff" => {
+                settings.restart_backoff.to_value()
+            }
+            "error-on-nonzero-exit" => {
+                settings.error_on_nonzero_exit.to_value()
+            }
+            "write-timeout" => {
+                settings.write_timeout.to_value()
+            }
+            "stall-action" => {
+                settings.stall_action.to_value()
+            }
+            "max-queued-buffers" => {
+                settings.max_queued_buffers.to_value()
+            }
+            "max-queued-bytes" => {
+                settings.max_queued_bytes.to_value()
+            }
+            "leaky" => {
+                settings.leaky.to_value()
+            }
+            "framing" => {
+                settings.framing.to_value()
+            }
+            "pack-planes" => {
+                settings.pack_planes.to_value()
+            }
+            "caps" => {
+                settings.caps.to_value()
+            }
+            "transport" => {
+                settings.transport.to_value()
+            }
+            "fifo-path" => {
+                settings.fifo_path.to_value()
+            }
+            "fifo-open-timeout" => {
+                settings.fifo_open_timeout.to_value()
+            }
+            "extra-fds" => {
+                gst::Array::new(&settings.extra_fds).to_value()
+            }
+            "socket-path" => {
+                settings.socket_path.to_value()
+            }
+            "shm-slots" => {
+                settings.shm_slots.to_value()
+            }
+            "shm-slot-size" => {
+                settings.shm_slot_size.to_value()
+            }
+            "vmsplice" => {
+                settings.vmsplice.to_value()
+            }
+            "pipe-size" => {
+                settings.pipe_size.to_value()
+            }
+            _ => unimplemented!(),
+        }
+    }
+}
+
+impl GstObjectImpl for PipeSink {}
+
+impl ElementImpl for PipeSink {}
+
+impl PipeSink {
+    // Spawn the subprocess for the given caps and start monitoring its output, or connect to the
+    // socket with transport=unix-socket
+    fn spawn_child(
+        &self,
+        state: &mut State,
+        settings: &Settings,
+        caps: &gst::CapsRef,
+    ) -> Result<(), gst::ErrorMessage> {
+        if settings.transport == Transport::UnixSocket {
+            return self.connect_socket(state, settings, caps);
+        }
+
+        let mut vars = Variables::from_caps(caps).map_err(|err| {
+            gst::error_msg!(
+                gst::CoreError::Negotiation,
+                ["Failed to parse caps {}: {}", caps, err]
+            )
+        })?;
+        // Unpacked frames are written with the padding of the caps
+        if !settings.pack_planes && settings.framing() != Framing::Y4m {
+            if let Ok(info) = gst_video::VideoInfo::from_caps(caps) {
+                vars.insert("frame_size", info.size().to_string());
+            }
+        }
+
+        let fifo = match settings.transport {
+            Transport::Stdin | Transport::UnixSocket | Transport::Shm => None,
+            Transport::Fifo => {
+                let fifo = Fifo::create(settings.fifo_path.as_deref().map(Path::new)).map_err(|err| {
+                    gst::error_msg!(
+                        gst::ResourceError::OpenWrite,
+                        ["Failed to create FIFO: {}", err]
+                    )
+                })?;
+                vars.insert("fifo", fifo.path().to_string_lossy().into_owned());
+                Some(fifo)
+            }
+        };
+
+        let roles = FdRole::parse_list(&settings.extra_fds).map_err(|err| {
+            gst::error_msg!(gst::ResourceError::Settings, ["{}", err])
+        })?;
+        let mut extra_fds = ExtraFds::new(&roles).map_err(|err| {
+            gst::error_msg!(
+                gst::ResourceError::Failed,
+                ["Failed to create extra pipes: {}", err]
+            )
+        })?;
+        for (name, path) in extra_fds.placeholders() {
+            vars.insert(name, path);
+        }
+
+        let ring = match settings.transport {
+            Transport::Shm => {
+                let ring = self.create_ring(settings, caps)?;
+                let fd = extra_fds.push(ring.as_fd()).map_err(|err| {
+                    gst::error_msg!(
+                        gst::ResourceError::Failed,
+                        ["Failed to pass the ring buffer to the subprocess: {}", err]
+                    )
+                })?;
+                vars.insert("shm_fd", fd.to_string());
+                Some(ring)
+            }
+            _ => None,
+        };
+
+        let stream_header = settings.framing().stream_header(caps, state.caps_seq).map_err(|err| {
+            gst::error_msg!(gst::CoreError::Negotiation, ["{}", err])
+        })?;
+
+        let (mut command, command_line) = settings.command.command(&vars)?;
+        extra_fds.inherit(&mut command);
+
+        let current_dir = settings.command.working_directory()?;
+
+        gst::info!(CAT, imp = self, "Starting subprocess with command: {}", command_line);
+
+        // Stdin stays free for the subprocess' own use with other transports, e.g. ffmpeg reads
+        // commands from it
+        let stdin = match settings.transport {
+            Transport::Fifo => Stdio::null(),
+            _ => Stdio::piped(),
+        };
+
+        // Create command
+        let mut child = command
+            .current_dir(current_dir)
+            .stdin(stdin)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| {
+                gst::error_msg!(
+                    gst::ResourceError::Failed,
+                    ["Failed to start process: {}", e]
+                )
+            })?;
+
+        let pid = child.id();
+        let extra_pipes = extra_fds.into_pipes();
+
+        // Setup stdout monitoring
+        let stdout = child.stdout.take().unwrap();
+
+        let stdout_thread = thread::spawn({
+            let this = self.downgrade();
+            move || {
+                use std::io::BufRead;
+                let reader = std::io::BufReader::new(stdout);
+                for line in reader.lines().map_while(Result::ok) {
+                    let this = match this.upgrade() {
+                        Some(this) => this,
+                        None => return,
+                    };
+                    gst::debug!(CAT, imp = this, "stdout: {}", line);
+                }
+            }
+        });
+
+        // Setup stderr monitoring
+        let stderr = child.stderr.take().unwrap();
+
+        let stderr_thread = thread::spawn({
+            let this = self.downgrade();
+            move || {
+                use std::io::BufRead;
+                let reader = std::io::BufReader::new(stderr);
+                for line in reader.lines().map_while(Result::ok) {
+                    let this = match this.upgrade() {
+                        Some(this) => this,
+                        None => return,
+                    };
+                    gst::warning!(CAT, imp = this, "stderr: {}", line);
+                }
+            }
+        });
+
+        let mut metadata = None;
+        let mut message_threads = Vec::new();
+        for pipe in extra_pipes {
+            match pipe.role {
+                FdRole::Metadata => metadata = Some(MetadataWriter::new(pipe.file)),
+                FdRole::Messages => {
+                    message_threads.push(thread::spawn({
+                        let this = self.downgrade();
+                        move || {
+                            use std::io::BufRead;
+                            let reader = std::io::BufReader::new(pipe.file);
+                            for line in reader.lines().map_while(Result::ok) {
+                                let this = match this.upgrade() {
+                                    Some(this) => this,
+                                    None => return,
+                                };
+                                gst::debug!(CAT, imp = this, "fd {}: {}", pipe.fd, line);
+                                this.post_subprocess_message(pid, pipe.fd, &line);
+                            }
+                        }
+                    }));
+                }
+            }
+        }
+
+        state.child_process = Some(child);
+        state.fifo = fifo;
+        state.ring = ring;
+        self.stats.lock().unwrap().pid = Some(pid);
+        state.started_at = Some(Instant::now());
+        state.stdout_thread = Some(stdout_thread);
+        state.stderr_thread = Some(stderr_thread);
+        state.message_threads = message_threads;
+        state.metadata = metadata;
+        state.cmd = command_line;
+
+        gst::info!(CAT, imp = self, "Started subprocess with PID: {}", pid);
+
+        let res = self
+            .open_input(state, settings)
+            .and_then(|input| self.setup_input(state, settings, input, stream_header));
+        if let Err(err) = res {
+            self.stop_child(state, settings);
+            return Err(err);
+        }
+
+        Ok(())
+    }
+
+    // Create the ring buffer of transport=shm, with slots for the frames of the caps by default
+    fn create_ring(&self, settings: &Settings, caps: &gst::CapsRef) -> Result<Ring, gst::ErrorMessage> {
+        let slot_size = match settings.shm_slot_size {
+            0 => gst_video::VideoInfo::from_caps(caps).map(|info| info.size()).map_err(|_| {
+                gst::error_msg!(
+                    gst::ResourceError::Settings,
+                    ["shm-slot-size is required for caps {}", caps]
+                )
+            })?,
+            size => usize::try_from(size).unwrap_or(usize::MAX),
+        };
+
+        let ring = Ring::create(settings.shm_slots, slot_size).map_err(|err| {
+            gst::error_msg!(
+                gst::ResourceError::Failed,
+                ["Failed to create ring buffer: {}", err]
+            )
+        })?;
+        gst::debug!(CAT, imp = self, "Created ring buffer of {} slots of {} bytes", settings.shm_slots, slot_size);
+
+        Ok(ring)
+    }
+
+    // Connect to the socket of transport=unix-socket, which takes the place of the subprocess
+    fn connect_socket(
+        &self,
+        state: &mut State,
+        settings: &Settings,
+        caps: &gst::CapsRef,
+    ) -> Result<(), gst::ErrorMessage> {
+        let Some(path) = settings.socket_path.as_deref() else {
+            return Err(gst::error_msg!(
+                gst::ResourceError::Settings,
+                ["socket-path is required with transport=unix-socket"]
+            ));
+        };
+
+        let stream_header = settings.framing.stream_header(caps, state.caps_seq).map_err(|err| {
+            gst::error_msg!(gst::CoreError::Negotiation, ["{}", err])
+        })?;
+
+        let stream = transport::connect(path).map_err(|err| {
+            gst::error_msg!(
+                gst::ResourceError::OpenWrite,
+                ["Failed to connect to {}: {}", path, err]
+            )
+        })?;
+
+        state.connected = true;
+        state.cmd = path.to_string();
+
+        gst::info!(CAT, imp = self, "Connected to {}", path);
+
+        if let Err(err) = self.setup_input(state, settings, Input::Socket(stream), stream_header) {
+            self.stop_child(state, settings);
+            return Err(err);
+        }
+
+        Ok(())
+    }
+
+    // Open the pipe the frames are written to, in non-blocking mode so that unlock() can
+    // interrupt writes
+    fn open_input(&self, state: &mut State, settings: &Settings) -> Result<Input, gst::ErrorMessage> {
+        let child = state.child_process.as_mut().unwrap();
+
+        let input = match &state.fifo {
+            Some(fifo) => {
+                gst::debug!(CAT, imp = self, "Waiting for the subprocess to open FIFO {}", fifo.path().display());
+                match fifo.open_writer(child, &self.waker, settings.fifo_open_timeout.into()) {
+                    Ok(input) => input,
+                    Err(WriteError::TimedOut(_)) => {
+                        return Err(gst::error_msg!(
+                            gst::ResourceError::OpenWrite,
+                            [
+                                "Subprocess didn't open FIFO {} within {}",
+                                fifo.path().display(),
+                                settings.fifo_open_timeout
+                            ]
+                        ));
+                    }
+                    Err(WriteError::Io(err)) => {
+                        return Err(gst::error_msg!(
+                            gst::ResourceError::OpenWrite,
+                            ["Failed to open FIFO {}: {}", fifo.path().display(), err]
+                        ));
+                    }
+                    Err(_) => {
+                        return Err(gst::error_msg!(
+                            gst::ResourceError::OpenWrite,
+                            ["Interrupted while waiting for the subprocess to open FIFO {}", fifo.path().display()]
+                        ));
+                    }
+                }
+            }
+            None => {
+                let input = File::from(OwnedFd::from(child.stdin.take().unwrap()));
+                writer::set_nonblocking(input.as_fd()).map_err(|err| {
+                    gst::error_msg!(
+                        gst::ResourceError::Failed,
+                        ["Failed to make stdin non-blocking: {}", err]
+                    )
+                })?;
+                input
+            }
+        };
+
+        if settings.pipe_size > 0 {
+            match splice::set_pipe_size(input.as_fd(), settings.pipe_size) {
+                Ok(size) => gst::debug!(CAT, imp = self, "Resized input pipe to {} bytes", size),
+                Err(err) => {
+                    gst::warning!(CAT, imp = self, "Failed to resize input pipe to {} bytes: {}", settings.pipe_size, err);
+                }
+            }
+        }
+
+        Ok(Input::Pipe(input))
+    }
+
+    // Start writing to `input`, from a writer thread if buffers are queued
+    fn setup_input(
+        &self,
+        state: &mut State,
+        settings: &Settings,
+        input: Input,
+        stream_header: Option<Vec<u8>>,
+    ) -> Result<(), gst::ErrorMessage> {
+        let splicer = match input {
+            Input::Pipe(_) if settings.vmsplice => Some(Splicer::new()),
+            _ => None,
+        };
+
+        let limits = settings.queue_limits();
+        if limits.is_enabled() {
+            let queue = FrameQueue::new(limits, self.waker.is_flushing()).map_err(|err| {
+                gst::error_msg!(
+                    gst::ResourceError::Failed,
+                    ["Failed to create writer queue: {}", err]
+                )
+            })?;
+            let queue = Arc::new(queue);
+
+            // The writer thread writes the stream header so that it can't be dropped by leaky
+            let metadata = state.metadata.take();
+            state.writer_thread = Some(self.spawn_writer(input, metadata, splicer, queue.clone(), stream_header));
+            *self.queue.lock().unwrap() = Some(queue);
+        } else {
+            state.input = Some(input);
+            state.splicer = splicer;
+            state.stream_header = stream_header;
+        }
+
+        Ok(())
+    }
+
+    // Write queued buffers to the subprocess until the queue is finished or shut down
+    fn spawn_writer(
+        &self,
+        mut input: Input,
+        mut metadata: Option<MetadataWriter>,
+        mut splicer: Option<Splicer>,
+        queue: Arc<FrameQueue>,
+        stream_header: Option<Vec<u8>>,
+    ) -> thread::JoinHandle<Option<Splicer>> {
+        let this = self.downgrade();
+
+        thread::spawn(move || {
+            let mut stream_header = stream_header;
+
+            while let Some((header, buffer)) = queue.pop() {
+                let write_start = Instant::now();
+
+                if let Some(writer) = &mut metadata {
+                    match writer.write(&buffer, queue.waker()) {
+                        Ok(()) => (),
+                        Err(WriteError::Flushing) => break,
+                        Err(err) => {
+                            if let Some(this) = this.upgrade() {
+                                gst::warning!(CAT, imp = this, "Failed to write frame metadata, not writing it anymore: {:?}", err);
+                            }
+                            metadata = None;
+                        }
+                    }
+                }
+
+                let mut result = Ok(());
+                for header in stream_header.take().into_iter().chain(header) {
+                    result = match &mut splicer {
+                        Some(splicer) => splicer.write_all(&mut input, &header, queue.waker(), None),
+                        None => writer::write_all(&mut input, &header, queue.waker(), None),
+                    };
+                    if result.is_err() {
+                        break;
+                    }
+                }
+                if result.is_ok() {
+                    result = match &mut splicer {
+                        Some(splicer) => splicer.write_buffer(&mut input, &buffer, 0, queue.waker(), None),
+                        None => writer::write_buffer(&mut input, &buffer, 0, queue.waker(), None),
+                    };
+                }
+
+                let Some(this) = this.upgrade() else {
+                    break;
+                };
+
+                match result {
+                    Ok(()) => {
+                        this.stats.lock().unwrap().record_write(buffer.size(), write_start.elapsed());
+                        gst::trace!(CAT, imp = this, "Wrote buffer of size {}", buffer.size());
+                    }
+                    Err(WriteError::Flushing) => {
+                        gst::debug!(CAT, imp = this, "Writer interrupted");
+                        break;
+                    }
+                    Err(err) => {
+                        gst::error!(CAT, imp = this, "Failed to write to process input: {:?}", err);
+                        break;
+                    }
+                }
+            }
+
+            // Dropping the input closes the pipe
+            queue.close();
+            splicer
+        })
+    }
+
+    // Stop the writer thread, discarding all queued buffers
+    fn stop_writer(&self, state: &mut State) {
+        if let Some(queue) = self.queue.lock().unwrap().take() {
+            queue.shutdown();
+        }
+
+        if let Some(thread) = state.writer_thread.take() {
+            if let Some(splicer) = thread.join().unwrap() {
+                state.splicer = Some(splicer);
+            }
+        }
+    }
+
+    // Called from the streaming thread when the subprocess exited. Restarts it according to the
+    // restart policy, or fails the flow.
+    fn handle_child_exit(&self, state: &mut State, status: ExitStatus) -> Result<(), gst::FlowError> {
+        // Not holding the settings lock, messages are posted from here
+        let settings = self.settings.lock().unwrap().clone();
+        let pid = state.child_process.as_ref().map(|c| c.id()).unwrap_or_default();
+
+        let restart = match settings.restart_policy {
+            RestartPolicy::Never => false,
+            RestartPolicy::OnFailure => !status.success(),
+            RestartPolicy::Always => true,
+        };
+        let exhausted = settings.max_restarts >= 0 && state.restarts >= settings.max_restarts as u32;
+
+        if !restart || exhausted {
+            // Process has exited unexpectedly
+            gst::error!(CAT, imp = self, "Subprocess (PID: {}) exited unexpectedly", pid);
+
+            if let Some(code) = status.code() {
+                gst::error!(CAT, imp = self, "Exit code: {}", code);
+            } else {
+                gst::error!(CAT, imp = self, "Process terminated by signal");
+            }
+
+            if restart {
+                gst::error!(CAT, imp = self, "Giving up after {} restarts", state.restarts);
+            }
+
+            self.stop_child(state, &settings);
+            return Err(gst::FlowError::Error);
+        }
+
+        gst::warning!(
+            CAT,
+            imp = self,
+            "Subprocess (PID: {}) exited with {}, restarting in {}",
+            pid,
+            status,
+            settings.restart_backoff
+        );
+
+        self.stop_child(state, &settings);
+
+        let exit_code = status.code().unwrap_or(-1);
+        if !self.waker.sleep(settings.restart_backoff.into()) {
+            gst::debug!(CAT, imp = self, "Flushing, restarting the subprocess with the next buffer");
+            state.pending_restart = Some((pid, exit_code));
+            return Err(gst::FlowError::Flushing);
+        }
+
+        self.restart_child(state, &settings, pid, exit_code)
+    }
+
+    // Spawn the subprocess again after the previous one with `pid` exited with `exit_code`
+    fn restart_child(
+        &self,
+        state: &mut State,
+        settings: &Settings,
+        pid: u32,
+        exit_code: i32,
+    ) -> Result<(), gst::FlowError> {
+        state.pending_restart = None;
+        state.drained = false;
+
+        let Some(caps) = state.caps.clone() else {
+            gst::error!(CAT, imp = self, "Can't restart subprocess without caps");
+            return Err(gst::FlowError::NotNegotiated);
+        };
+
+        if let Err(err) = self.spawn_child(state, settings, &caps) {
+            self.post_error_message(err);
+            return Err(gst::FlowError::Error);
+        }
+        state.restarts += 1;
+        self.stats.lock().unwrap().restarts = state.restarts;
+
+        let new_pid = state.child_process.as_ref().map(|c| c.id()).unwrap_or_default();
+        let s = gst::Structure::builder("subprocess-restarted")
+            .field("pid", new_pid)
+            .field("previous-pid", pid)
+            .field("exit-code", exit_code)
+            .field("restart-count", state.restarts)
+            .build();
+        let _ = self
+            .obj()
+            .post_message(gst::message::Element::builder(s).src(&*self.obj()).build());
+
+        Ok(())
+    }
+
+    // Spawn the subprocess again with the current caps, after the previous one was drained on EOS
+    fn respawn_child(&self, state: &mut State, settings: &Settings) -> Result<(), gst::FlowError> {
+        // Still running if it didn't exit within eos-timeout or the drain was interrupted
+        self.stop_child(state, settings);
+        state.drained = false;
+
+        let Some(caps) = state.caps.clone() else {
+            gst::error!(CAT, imp = self, "Can't spawn subprocess without caps");
+            return Err(gst::FlowError::NotNegotiated);
+        };
+
+        gst::info!(CAT, imp = self, "Data after EOS, spawning subprocess again");
+        if let Err(err) = self.spawn_child(state, settings, &caps) {
+            self.post_error_message(err);
+            return Err(gst::FlowError::Error);
+        }
+
+        Ok(())
+    }
+
+    // Called from the streaming thread when the peer closed the connection of
+    // transport=unix-socket. Reconnects with a backoff starting at restart-backoff and doubling
+    // after every failed attempt, until max-restarts attempts in a row failed.
+    fn handle_disconnect(&self, state: &mut State) -> Result<(), gst::FlowError> {
+        let settings = self.settings.lock().unwrap().clone();
+
+        self.stop_child(state, &settings);
+
+        let Some(caps) = state.caps.clone() else {
+            gst::error!(CAT, imp = self, "Can't reconnect without caps");
+            return Err(gst::FlowError::NotNegotiated);
+        };
+
+        let mut backoff = settings.restart_backoff;
+        let mut attempts = 0;
+        loop {
+            gst::warning!(CAT, imp = self, "Connection closed, reconnecting in {}", backoff);
+            if !self.waker.sleep(backoff.into()) {
+                gst::debug!(CAT, imp = self, "Flushing, not reconnecting");
+                return Err(gst::FlowError::Flushing);
+            }
+
+            attempts += 1;
+            match self.connect_socket(state, &settings, &caps) {
+                Ok(()) => break,
+                Err(err) if settings.max_restarts >= 0 && attempts >= settings.max_restarts as u32 => {
+                    gst::error!(CAT, imp = self, "Giving up after {} reconnection attempts", attempts);
+                    self.post_error_message(err);
+                    return Err(gst::FlowError::Error);
+                }
+                Err(err) => {
+                    gst::debug!(CAT, imp = self, "Failed to reconnect: {}", err);
+                    backoff = backoff
+                        .saturating_mul(2)
+                        .clamp(RECONNECT_BACKOFF_MIN, RECONNECT_BACKOFF_MAX);
+                }
+            }
+        }
+
+        state.restarts += 1;
+        self.stats.lock().unwrap().restarts = state.restarts;
+
+        let s = gst::Structure::builder("socket-reconnected")
+            .field("socket-path", &state.cmd)
+            .field("attempts", attempts)
+            .field("restart-count", state.restarts)
+            .build();
+        let _ = self
+            .obj()
+            .post_message(gst::message::Element::builder(s).src(&*self.obj()).build());
+
+        Ok(())
+    }
+
+    // Close stdin on EOS and wait for the subprocess to finish its output. Returns false if
+    // the subprocess failed and EOS should not be forwarded.
+    fn drain_child(&self) -> bool {
+        let settings = self.settings.lock().unwrap().clone();
+        let mut state = self.state.lock().unwrap();
+
+        if !state.is_running() {
+            return true;
+        }
+
+        // Also if the drain is cut short, e.g. by a flushing seek, the subprocess got EOF or is
+        // about to, so data after EOS is written to a new one
+        state.drained = true;
+
+        // Let the writer thread write all queued buffers before it closes the input
+        let queue = self.queue.lock().unwrap().clone();
+        if let Some(queue) = queue {
+            queue.finish();
+            if !queue.wait_closed(settings.eos_timeout.into()) {
+                if self.waker.is_flushing() {
+                    gst::debug!(CAT, imp = self, "Flushing, not waiting for queued buffers");
+                    return false;
+                }
+                gst::warning!(CAT, imp = self, "Queued buffers not written within {} after EOS", settings.eos_timeout);
+            }
+            self.stop_writer(&mut state);
+        }
+
+        drop(state.input.take());
+
+        // Closing the connection is all there is to do for a socket
+        if state.connected {
+            gst::debug!(CAT, imp = self, "EOS, closed connection to {}", state.cmd);
+            self.stop_child(&mut state, &settings);
+            return true;
+        }
+
+        let child = state.child_process.as_mut().unwrap();
+        let pid = child.id();
+        gst::debug!(CAT, imp = self, "EOS, closed input of process (PID: {})", pid);
+
+        // unlock() interrupts the wait, so that flushes and state changes don't wait for the
+        // subprocess
+        match wait_interruptible(child, settings.eos_timeout, &self.waker) {
+            Ok(Some(_)) => (),
+            Ok(None) if self.waker.is_flushing() => {
+                gst::debug!(CAT, imp = self, "Flushing, not waiting for process (PID: {}) to exit", pid);
+                return false;
+            }
+            Ok(None) => {
+                gst::warning!(
+                    CAT,
+                    imp = self,
+                    "Process (PID: {}) did not exit within {} after EOS",
+                    pid,
+                    settings.eos_timeout
+                );
+                return true;
+            }
+            Err(err) => {
+                gst::warning!(CAT, imp = self, "Failed to wait for child process (PID: {}): {}", pid, err);
+                return true;
+            }
+        }
+
+        let exit_status = self.stop_child(&mut state, &settings);
+
+        if let Some(code) = exit_status.and_then(|status| status.code()) {
+            if code != 0 && settings.error_on_nonzero_exit {
+                gst::element_imp_error!(
+                    self,
+                    gst::ResourceError::Failed,
+                    ["Subprocess exited with code {}", code]
+                );
+                return false;
+            }
+        }
+
+        true
+    }
+
+    // Apply stall-action to a write that timed out after `written` of `size` bytes. Returns the
+    // result of render() if the buffer is done with, or None if it should be written without a
+    // timeout.
+    fn handle_stall(
+        &self,
+        pid: u32,
+        written: usize,
+        size: usize,
+        action: StallAction,
+    ) -> Option<Result<gst::FlowSuccess, gst::FlowError>> {
+        gst::warning!(CAT, imp = self, "Subprocess (PID: {}) stalled, wrote {} of {} bytes", pid, written, size);
+        self.post_stall(pid, written, size, action);
+
+        match action {
+            StallAction::Drop if written == 0 => {
+                self.stats.lock().unwrap().buffers_dropped += 1;
+                gst::debug!(CAT, imp = self, "Dropping buffer of {} bytes", size);
+                return Some(Ok(gst::FlowSuccess::Ok));
+            }
+            StallAction::Drop => {
+                // Dropping the rest of the buffer would break the framing for the subprocess
+                gst::debug!(CAT, imp = self, "Buffer partially written, completing it");
+            }
+            StallAction::Warn => {
+                gst::element_imp_warning!(
+                    self,
+                    gst::ResourceError::Write,
+                    ["Subprocess (PID: {}) is not reading its input", pid]
+                );
+            }
+            StallAction::Error => {
+                gst::element_imp_error!(
+                    self,
+                    gst::ResourceError::Write,
+                    ["Subprocess (PID: {}) is not reading its input", pid]
+                );
+                return Some(Err(gst::FlowError::Error));
+            }
+        }
+
+        None
+    }
+
+    // Post a `subprocess-stalled` element message when a write timed out
+    fn post_stall(&self, pid: u32, written: usize, size: usize, action: StallAction) {
+        let s = gst::Structure::builder("subprocess-stalled")
+            .field("pid", pid)
+            .field("bytes-written", written as u64)
+            .field("buffer-size", size as u64)
+            .field("action", action)
+            .build();
+        let _ = self
+            .obj()
+            .post_message(gst::message::Element::builder(s).src(&*self.obj()).build());
+    }
+
+    // Post a `subprocess-stopping` element message for a step of the shutdown sequence
+    fn post_stop_step(&self, pid: u32, step: &str, signal: Option<libc::c_int>) {
+        let s = gst::Structure::builder("subprocess-stopping")
+            .field("pid", pid)
+            .field("step", step)
+            .field_if_some("signal", signal)
+            .build();
+        let _ = self
+            .obj()
+            .post_message(gst::message::Element::builder(s).src(&*self.obj()).build());
+    }
+
+    // Post a `subprocess-message` element message for a line read from a messages pipe
+    fn post_subprocess_message(&self, pid: u32, fd: i32, line: &str) {
+        let s = gst::Structure::builder("subprocess-message")
+            .field("pid", pid)
+            .field("fd", fd)
+            .field("line", line)
+            .build();
+        let _ = self
+            .obj()
+            .post_message(gst::message::Element::builder(s).src(&*self.obj()).build());
+    }
+
+    // Post a `subprocess-exited` element message once the subprocess was reaped
+    fn post_exit_status(&self, pid: u32, status: ExitStatus, runtime: gst::ClockTime) {
+        let s = gst::Structure::builder("subprocess-exited")
+            .field("pid", pid)
+            .field("exit-code", status.code().unwrap_or(-1))
+            .field("signal", status.signal().unwrap_or(0))
+            .field("core-dumped", status.core_dumped())
+            .field("runtime", runtime)
+            .build();
+        let _ = self
+            .obj()
+            .post_message(gst::message::Element::builder(s).src(&*self.obj()).build());
+    }
+
+    // Close the input, reap the subprocess, remove the FIFO and join its output monitoring
+    // threads.
+    //
+    // The subprocess gets `wait-for-exit` to exit on its own after its input is closed, then
+    // `kill-timeout` after receiving `stop-signal`, and is finally killed with SIGKILL.
+    fn stop_child(&self, state: &mut State, settings: &Settings) -> Option<ExitStatus> {
+        let mut exit_status = None;
+
+        self.stop_writer(state);
+
+        // Close the input to send EOF
+        drop(state.input.take());
+        drop(state.metadata.take());
+
+        // Stop child process
+        if let Some(mut child) = state.child_process.take() {
+            self.stats.lock().unwrap().pid = None;
+            let pid = child.id();
+
+            // Nothing to do if the process already exited, its PID may have been reused
+            let mut status = child.try_wait();
+
+            if matches!(status, Ok(None)) {
+                gst::debug!(CAT, imp = self, "Closed input of process (PID: {}), waiting for it to exit", pid);
+                self.post_stop_step(pid, "stdin-closed", None);
+                status = wait_timeout(&mut child, settings.wait_for_exit);
+            }
+
+            if matches!(status, Ok(None)) {
+                let signal = settings.stop_signal.as_raw();
+                gst::info!(CAT, imp = self, "Process (PID: {}) still running, sending {:?}", pid, settings.stop_signal);
+                self.post_stop_step(pid, "signal", Some(signal));
+
+                unsafe {
+                    libc::kill(pid as libc::pid_t, signal);
+                }
+                status = wait_timeout(&mut child, settings.kill_timeout);
+            }
+
+            if matches!(status, Ok(None)) {
+                gst::warning!(CAT, imp = self, "Process (PID: {}) ignored {:?}, killing it", pid, settings.stop_signal);
+                self.post_stop_step(pid, "kill", Some(libc::SIGKILL));
+
+                if let Err(err) = child.kill() {
+                    gst::warning!(CAT, imp = self, "Failed to kill process (PID: {}): {}", pid, err);
+                }
+                status = child.wait().map(Some);
+            }
+
+            match status {
+                Ok(Some(status)) => {
+                    if let Some(code) = status.code() {
+                        gst::info!(CAT, imp = self, "Process (PID: {}) exited with code {}", pid, code);
+                    } else {
+                        gst::info!(CAT, imp = self, "Process (PID: {}) terminated by signal", pid);
+                    }
+
+                    let runtime = state
+                        .started_at
+                        .take()
+                        .map(|started_at| gst::ClockTime::try_from(started_at.elapsed()).unwrap())
+                        .unwrap_or_default();
+                    self.post_exit_status(pid, status, runtime);
+                    exit_status = Some(status);
+                }
+                Ok(None) => unreachable!(),
+                Err(err) => {
+                    gst::warning!(CAT, imp = self, "Failed to wait for child process (PID: {}): {}", pid, err);
+                }
+            }
+        }
+
+        // The pipe is gone with the subprocess, so nothing references the spliced buffers anymore
+        if let Some(splicer) = state.splicer.take() {
+            gst::trace!(CAT, imp = self, "Releasing {} spliced buffers", splicer.pending());
+        }
+
+        // Unlinks the FIFO and unmaps the ring buffer
+        state.fifo = None;
+        state.ring = None;
+        state.connected = false;
+
+        // Join stdout and stderr threads
+        if let Some(thread) = state.stdout_thread.take() {
+            thread.join().unwrap();
+        }
+
+        if let Some(thread) = state.stderr_thread.take() {
+            thread.join().unwrap();
+        }
+
+        for thread in state.message_threads.drain(..) {
+            thread.join().unwrap();
+        }
+
+        exit_status
+    }
+}
+
+impl BaseSinkImpl for PipeSink {
+    fn event(&self, event: gst::Event) -> bool {
+        if let gst::EventView::Eos(..) = event.view() {
+            // Only forward EOS once the subprocess finished its output
+            if !self.drain_child() {
+                return false;
+            }
+        }
+
+        self.parent_event(event)
+    }
+
+    fn caps(&self, filter: Option<&gst::Caps>) -> Option<gst::Caps> {
+        let mut caps = self.obj().sink_pad().pad_template_caps();
+
+        if let Some(allowed) = &self.settings.lock().unwrap().caps {
+            caps = allowed.intersect_with_mode(&caps, gst::CapsIntersectMode::First);
+        }
+
+        if let Some(filter) = filter {
+            caps = filter.intersect_with_mode(&caps, gst::CapsIntersectMode::First);
+        }
+
+        gst::log!(CAT, imp = self, "Returning caps {}", caps);
+        Some(caps)
+    }
+
+    fn fixate(&self, mut caps: gst::Caps) -> gst::Caps {
+        caps.truncate();
+
+        {
+            let caps = caps.make_mut();
+            let s = caps.structure_mut(0).unwrap();
+            if s.name() == "video/x-raw" {
+                s.fixate_field_nearest_int("width", 640);
+                s.fixate_field_nearest_int("height", 480);
+                if s.has_field("framerate") {
+                    s.fixate_field_nearest_fraction("framerate", gst::Fraction::new(30, 1));
+                }
+            } else if s.name() == "audio/x-raw" {
+                s.fixate_field_nearest_int("rate", 48000);
+                s.fixate_field_nearest_int("channels", 2);
+            }
+        }
+
+        self.parent_fixate(caps)
+    }
+
+    fn set_caps(&self, caps: &gst::Caps) -> Result<(), gst::LoggableError> {
+        gst::debug!(CAT, imp = self, "Caps set to: {}", caps);
+
+        // Not locked while spawning, which may wait for the subprocess to open the FIFO
+        let settings = self.settings.lock().unwrap().clone();
+        let mut state = self.state.lock().unwrap();
+
+        if let Err(err) = settings.framing().stream_header(caps, state.caps_seq) {
+            gst::element_imp_error!(self, gst::CoreError::Negotiation, ["{}", err]);
+            return Err(gst::loggable_error!(CAT, "Caps not supported by framing"));
+        }
+
+        let caps_changed = state.caps.as_ref().is_some_and(|current| current != caps);
+        if state.caps.as_ref() != Some(caps) {
+            state.caps_seq = state.caps_seq.wrapping_add(1);
+        }
+
+        // New caps after EOS, a drained subprocess doesn't take more data
+        if state.drained {
+            self.stop_child(&mut state, &settings);
+            state.drained = false;
+        }
+
+        if state.is_running() && caps_changed {
+            match settings.on_caps_change {
+                CapsChange::Ignore => {
+                    gst::warning!(CAT, imp = self, "Caps changed, subprocess keeps running");
+                    if let Some(header) = settings.framing().caps_change_header(caps, state.caps_seq) {
+                        state.stream_header = Some(header);
+                    }
+                }
+                CapsChange::Restart => {
+                    gst::info!(CAT, imp = self, "Caps changed, restarting subprocess");
+                    self.stop_child(&mut state, &settings);
+                }
+                CapsChange::Error => {
+                    gst::element_imp_error!(
+                        self,
+                        gst::CoreError::Negotiation,
+                        ("Caps changed while the subprocess is running"),
+                        ["Old caps: {:?}, new caps: {}", state.caps, caps]
+                    );
+                    return Err(gst::loggable_error!(CAT, "Caps changed"));
+                }
+            }
+        }
+
+        // The subprocess is spawned on the first caps so that the command can be templated. This
+        // also takes care of a pending restart.
+        if !state.is_running() {
+            state.pending_restart = None;
+            if let Err(err) = self.spawn_child(&mut state, &settings, caps) {
+                self.post_error_message(err);
+                return Err(gst::loggable_error!(CAT, "Failed to spawn subprocess"));
+            }
+        }
+
+        state.caps = Some(caps.clone());
+        state.video_info = gst_video::VideoInfo::from_caps(caps).ok();
+        state.audio_info = gst_audio::AudioInfo::from_caps(caps).ok();
+        Ok(())
+    }
+
+    fn start(&self) -> Result<(), gst::ErrorMessage> {
+        let settings = self.settings.lock().unwrap();
+        let mut state = self.state.lock().unwrap();
+
+        if settings.transport == Transport::UnixSocket {
+            if settings.socket_path.is_none() {
+                return Err(gst::error_msg!(
+                    gst::ResourceError::Settings,
+                    ["socket-path is required with transport=unix-socket"]
+                ));
+            }
+            if !settings.extra_fds.is_empty() {
+                return Err(gst::error_msg!(
+                    gst::ResourceError::Settings,
+                    ["extra-fds can't be used with transport=unix-socket"]
+                ));
+            }
+        } else {
+            if settings.transport == Transport::Shm {
+                if !matches!(settings.framing, Framing::Raw | Framing::LengthPrefixed) {
+                    return Err(gst::error_msg!(
+                        gst::ResourceError::Settings,
+                        ["framing must be raw or length-prefixed with transport=shm"]
+                    ));
+                }
+                if settings.queue_limits().is_enabled() {
+                    return Err(gst::error_msg!(
+                        gst::ResourceError::Settings,
+                        ["The ring buffer of transport=shm can't be combined with a writer queue"]
+                    ));
+                }
+            }
+
+            settings.command.validate().inspect_err(|err| {
+                gst::debug!(CAT, imp = self, "Invalid command settings: {}", err);
+            })?;
+            settings.command.working_directory()?;
+        }
+        FdRole::parse_list(&settings.extra_fds).map_err(|err| {
+            gst::error_msg!(gst::ResourceError::Settings, ["{}", err])
+        })?;
+
+        // The subprocess itself is spawned once caps are known
+        state.caps = None;
+        state.video_info = None;
+        state.audio_info = None;
+        state.caps_seq = 0;
+        state.restarts = 0;
+        state.pending_restart = None;
+        state.drained = false;
+        *self.stats.lock().unwrap() = Stats::default();
+        self.waker.reset();
+
+        gst::info!(CAT, imp = self, "Started");
+        Ok(())
+    }
+
+    fn stop(&self) -> Result<(), gst::ErrorMessage> {
+        let settings = self.settings.lock().unwrap().clone();
+        let mut state = self.state.lock().unwrap();
+
+        let exit_status = self.stop_child(&mut state, &settings);
+        state.caps = None;
+        state.video_info = None;
+        state.audio_info = None;
+
+        if let Some(code) = exit_status.and_then(|status| status.code()) {
+            if code != 0 && settings.error_on_nonzero_exit {
+                return Err(gst::error_msg!(
+                    gst::ResourceError::Failed,
+                    ["Subprocess exited with code {}", code]
+                ));
+            }
+        }
+
+        gst::info!(CAT, imp = self, "Stopped");
+        Ok(())
+    }
+
+    fn render(&self, buffer: &gst::Buffer) -> Result<gst::FlowSuccess, gst::FlowError> {
+        let (write_timeout, stall_action, framing, pack_planes) = {
+            let settings = self.settings.lock().unwrap();
+            let write_timeout = Some(settings.write_timeout)
+                .filter(|timeout| !timeout.is_zero())
+                .map(Duration::from);
+            (write_timeout, settings.stall_action, settings.framing(), settings.pack_planes)
+        };
+
+        let mut state = self.state.lock().unwrap();
+
+        // Finish a restart that a flush interrupted during the backoff
+        if let Some((pid, exit_code)) = state.pending_restart {
+            let settings = self.settings.lock().unwrap().clone();
+            self.restart_child(&mut state, &settings, pid, exit_code)?;
+        }
+
+        // Data after EOS, e.g. after a flushing seek
+        if state.drained {
+            let settings = self.settings.lock().unwrap().clone();
+            self.respawn_child(&mut state, &settings)?;
+        }
+
+        // Check if the child process is still running, without waiting
+        let connected = state.connected;
+        let exit_status = match &mut state.child_process {
+            Some(c) => c.try_wait().map_err(|e| {
+                gst::error!(CAT, imp = self, "Failed to check subprocess status: {}", e);
+                gst::FlowError::Error
+            })?,
+            None if connected => None,
+            None => {
+                gst::error!(CAT, imp = self, "Child process not started");
+                return Err(gst::FlowError::Error);
+            }
+        };
+
+        if let Some(status) = exit_status {
+            self.handle_child_exit(&mut state, status)?;
+        }
+        // 0 for transport=unix-socket
+        let pid = state.child_process.as_ref().map_or(0, Child::id);
+
+        let converted;
+        let buffer = match (&state.video_info, &state.audio_info) {
+            // The Y4M header declares the packed frame size
+            (Some(info), _) if pack_planes || framing == Framing::Y4m => {
+                converted = pack::pack_planes(buffer, info).map_err(|err| {
+                    gst::error!(CAT, imp = self, "Failed to pack video frame: {}", err);
+                    gst::FlowError::Error
+                })?;
+                &converted
+            }
+            (_, Some(info)) if info.layout() == gst_audio::AudioLayout::NonInterleaved => {
+                converted = audio::interleave(buffer, info).map_err(|err| {
+                    gst::error!(CAT, imp = self, "Failed to interleave audio samples: {}", err);
+                    gst::FlowError::Error
+                })?;
+                &converted
+            }
+            _ => buffer,
+        };
+
+        let queue = self.queue.lock().unwrap().clone();
+        if let Some(queue) = queue {
+            // Only caps changes are pending here, the writer thread writes the initial header. The
+            // queue keeps the header if the buffer is dropped.
+            let header = state.stream_header.take();
+            match queue.push(framing.frame(buffer, state.caps_seq, None), header) {
+                Ok(0) => (),
+                Ok(dropped) => {
+                    self.stats.lock().unwrap().buffers_dropped += dropped as u64;
+                    gst::debug!(CAT, imp = self, "Writer queue full, dropped {} buffers", dropped);
+                }
+                Err(PushError::Flushing) => {
+                    gst::debug!(CAT, imp = self, "Flushing, buffer not queued");
+                    return Err(gst::FlowError::Flushing);
+                }
+                Err(PushError::Closed) if state.connected => {
+                    // The writer stops on write errors, most likely because the peer went away
+                    self.handle_disconnect(&mut state)?;
+                    self.stats.lock().unwrap().buffers_dropped += 1;
+                    gst::debug!(CAT, imp = self, "Dropped buffer queued for the closed connection");
+                    return Ok(gst::FlowSuccess::Ok);
+                }
+                Err(PushError::Closed) => {
+                    // The writer stops on write errors, most likely because the subprocess exited
+                    let wait_for_exit = self.settings.lock().unwrap().wait_for_exit;
+                    let child = state.child_process.as_mut().unwrap();
+                    if let Ok(Some(status)) = wait_timeout(child, wait_for_exit) {
+                        self.handle_child_exit(&mut state, status)?;
+                        self.stats.lock().unwrap().buffers_dropped += 1;
+                        gst::debug!(CAT, imp = self, "Dropped buffer queued for the exited subprocess");
+                        return Ok(gst::FlowSuccess::Ok);
+                    }
+
+                    gst::error!(CAT, imp = self, "Writer thread for process (PID: {}) stopped", pid);
+                    return Err(gst::FlowError::Error);
+                }
+            }
+
+            return Ok(gst::FlowSuccess::Ok);
+        }
+
+        // With transport=shm the frame is copied to the ring buffer, and only a slot record is
+        // written to stdin
+        let slot = match &mut state.ring {
+            Some(ring) => {
+                let mut result = ring.write(buffer, &self.waker, write_timeout);
+                if let Err(WriteError::TimedOut(_)) = result {
+                    if let Some(ret) = self.handle_stall(pid, 0, buffer.size(), stall_action) {
+                        return ret;
+                    }
+                    result = ring.write(buffer, &self.waker, None);
+                }
+
+                match result {
+                    Ok(slot) => Some(slot),
+                    Err(WriteError::Flushing) => {
+                        gst::debug!(CAT, imp = self, "Flushing, waiting for a free slot interrupted");
+                        return Err(gst::FlowError::Flushing);
+                    }
+                    Err(WriteError::TimedOut(_)) => unreachable!(),
+                    Err(WriteError::Io(err)) => {
+                        gst::element_imp_error!(
+                            self,
+                            gst::ResourceError::Write,
+                            ["Failed to copy buffer to the ring buffer: {}", err]
+                        );
+                        return Err(gst::FlowError::Error);
+                    }
+                }
+            }
+            None => None,
+        };
+
+        let framed = match slot {
+            Some(slot) => framing::slot_record(buffer, slot, state.caps_seq, state.stream_header.as_deref()),
+            None => framing.frame(buffer, state.caps_seq, state.stream_header.as_deref()),
+        };
+
+        // The subprocess only releases slots it got a record for, so the slot of a frame whose
+        // record is not completely written is released here
+        let release_slot = |ring: &Option<Ring>| {
+            if let (Some(ring), Some(slot)) = (ring, slot) {
+                ring.release(slot);
+            }
+        };
+
+        // Write to stdin or the FIFO
+        let State {
+            input,
+            splicer,
+            metadata,
+            ring,
+            ..
+        } = &mut *state;
+        let Some(input) = input.as_mut() else {
+            release_slot(ring);
+            gst::error!(CAT, imp = self, "Child process input closed");
+            return Err(gst::FlowError::Error);
+        };
+
+        // The metadata line goes first, so that the subprocess knows what the frame is about. A
+        // stalled frame is dropped before its metadata is written, once written the frame follows.
+        let write_start = Instant::now();
+        let mut frame_timeout = write_timeout;
+        if let Some(writer) = metadata {
+            match writer::wait_writable(input.as_fd(), &self.waker, write_timeout) {
+                Ok(()) | Err(WriteError::Io(_)) => (),
+                Err(WriteError::TimedOut(_)) => {
+                    if let Some(ret) = self.handle_stall(pid, 0, framed.size(), stall_action) {
+                        release_slot(ring);
+                        return ret;
+                    }
+                    frame_timeout = None;
+                }
+                Err(WriteError::Flushing) => {
+                    release_slot(ring);
+                    gst::debug!(CAT, imp = self, "Flushing, write interrupted");
+                    return Err(gst::FlowError::Flushing);
+                }
+            }
+
+            match writer.write(buffer, &self.waker) {
+                Ok(()) => (),
+                Err(WriteError::Flushing) => {
+                    release_slot(ring);
+                    gst::debug!(CAT, imp = self, "Flushing, metadata write interrupted");
+                    return Err(gst::FlowError::Flushing);
+                }
+                Err(err) => {
+                    gst::warning!(CAT, imp = self, "Failed to write frame metadata, not writing it anymore: {:?}", err);
+                    *metadata = None;
+                }
+            }
+        }
+
+        // Write frame data, the input is non-blocking so that unlock() can interrupt the write
+        let mut write = |offset, timeout| match splicer {
+            Some(splicer) => splicer.write_buffer(input, &framed, offset, &self.waker, timeout),
+            None => writer::write_buffer(input, &framed, offset, &self.waker, timeout),
+        };
+        let mut result = write(0, frame_timeout);
+
+        if let Err(WriteError::TimedOut(written)) = result {
+            if let Some(ret) = self.handle_stall(pid, written, framed.size(), stall_action) {
+                release_slot(ring);
+                return ret;
+            }
+            result = write(written, None);
+        }
+
+        // Anything but a dropped buffer wrote at least part of the stream header
+        state.stream_header = None;
+
+        if result.is_err() {
+            release_slot(&state.ring);
+        }
+
+        match result {
+            Ok(_) => {
+                // The frame itself with transport=shm, not its slot record
+                let size = if slot.is_some() { buffer.size() } else { framed.size() };
+                self.stats.lock().unwrap().record_write(size, write_start.elapsed());
+                gst::trace!(CAT, imp = self, "Wrote buffer of size {}", size);
+            }
+            Err(WriteError::Flushing) => {
+                gst::debug!(CAT, imp = self, "Flushing, write interrupted");
+                return Err(gst::FlowError::Flushing);
+            }
+            Err(WriteError::TimedOut(_)) => unreachable!(),
+            Err(WriteError::Io(e)) if state.connected && transport::is_disconnect(&e) => {
+                gst::warning!(CAT, imp = self, "Connection to {} closed: {}", state.cmd, e);
+                self.handle_disconnect(&mut state)?;
+                self.stats.lock().unwrap().buffers_dropped += 1;
+                gst::debug!(CAT, imp = self, "Dropped buffer written to the closed connection");
+                return Ok(gst::FlowSuccess::Ok);
+            }
+            Err(WriteError::Io(e)) if state.connected => {
+                gst::error!(CAT, imp = self, "Failed to write to {}: {}", state.cmd, e);
+                return Err(gst::FlowError::Error);
+            }
+            Err(WriteError::Io(e)) => {
+                // The subprocess may have exited while we were writing
+                let wait_for_exit = self.settings.lock().unwrap().wait_for_exit;
+                let child = state.child_process.as_mut().unwrap();
+                if let Ok(Some(status)) = wait_timeout(child, wait_for_exit) {
+                    self.handle_child_exit(&mut state, status)?;
+                    self.stats.lock().unwrap().buffers_dropped += 1;
+                    gst::debug!(CAT, imp = self, "Dropped buffer written to the exited subprocess");
+                    return Ok(gst::FlowSuccess::Ok);
+                }
+
+                gst::error!(CAT, imp = self, "Failed to write to process input: {}", e);
+                return Err(gst::FlowError::Error);
+            }
+        }
+
+        Ok(gst::FlowSuccess::Ok)
+    }
+
+    fn unlock(&self) -> Result<(), gst::ErrorMessage> {
+        gst::debug!(CAT, imp = self, "Unlocking");
+        self.waker.wake();
+        if let Some(queue) = &*self.queue.lock().unwrap() {
+            queue.set_flushing(true);
+        }
+        Ok(())
+    }
+
+    fn unlock_stop(&self) -> Result<(), gst::ErrorMessage> {
+        gst::debug!(CAT, imp = self, "Unlock stopped");
+        self.waker.reset();
+        if let Some(queue) = &*self.queue.lock().unwrap() {
+            queue.set_flushing(false);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct VideoPipeSink;
+
+#[glib::object_subclass]
+impl ObjectSubclass for VideoPipeSink {
+    const NAME: &'static str = "VideoPipeSink";
+    type Type = super::VideoPipeSink;
+    type ParentType = super::PipeSink;
+}
+
+impl ObjectImpl for VideoPipeSink {}
+
+impl GstObjectImpl for VideoPipeSink {}
+
+impl ElementImpl for VideoPipeSink {
+    fn metadata() -> Option<&'static gst::subclass::ElementMetadata> {
+        static ELEMENT_METADATA: Lazy<gst::subclass::ElementMetadata> = Lazy::new(|| {
+            gst::subclass::ElementMetadata::new(
+                "Vide Pipe Sink",
+                "Sink/Video",
+                "Pipes raw video frames to a provided subprocess",
+                "Rafael Caricio <rafael@caricio.com>",
+            )
+        });
+
+        Some(&*ELEMENT_METADATA)
+    }
+
+    fn pad_templates() -> &'static [gst::PadTemplate] {
+        static PAD_TEMPLATES: Lazy<Vec<gst::PadTemplate>> = Lazy::new(|| {
+            let caps = gst_video::VideoCapsBuilder::new().build();
+            let sink_pad_template = gst::PadTemplate::new(
+                "sink",
+                gst::PadDirection::Sink,
+                gst::PadPresence::Always,
+                &caps,
+            )
+            .unwrap();
+
+            vec![sink_pad_template]
+        });
+
+        PAD_TEMPLATES.as_ref()
+    }
+}
+
+impl BaseSinkImpl for VideoPipeSink {}
+
+impl PipeSinkImpl for VideoPipeSink {}
+
+#[derive(Default)]
+pub struct AudioPipeSink;
+
+#[glib::object_subclass]
+impl ObjectSubclass for AudioPipeSink {
+    const NAME: &'static str = "AudioPipeSink";
+    type Type = super::AudioPipeSink;
+    type ParentType = super::PipeSink;
+}
+
+impl ObjectImpl for AudioPipeSink {}
+
+impl GstObjectImpl for AudioPipeSink {}
+
+impl ElementImpl for AudioPipeSink {
+    fn metadata() -> Option<&'static gst::subclass::ElementMetadata> {
+        static ELEMENT_METADATA: Lazy<gst::subclass::ElementMetadata> = Lazy::new(|| {
+            gst::subclass::ElementMetadata::new(
+                "Audio Pipe Sink",
+                "Sink/Audio",
+                "Pipes raw audio samples to a provided subprocess",
+                "Rafael Caricio <rafael@caricio.com>",
+            )
+        });
+
+        Some(&*ELEMENT_METADATA)
+    }
+
+    fn pad_templates() -> &'static [gst::PadTemplate] {
+        static PAD_TEMPLATES: Lazy<Vec<gst::PadTemplate>> = Lazy::new(|| {
+            let sink_pad_template = gst::PadTemplate::new(
+                "sink",
+                gst::PadDirection::Sink,
+                gst::PadPresence::Always,
+                &audio::caps(),
+            )
+            .unwrap();
+
+            vec![sink_pad_template]
+        });
+
+        PAD_TEMPLATES.as_ref()
+    }
+}
+
+impl BaseSinkImpl for AudioPipeSink {}
+
+impl PipeSinkImpl for AudioPipeSink {}
@@ -0,0 +1,156 @@
+// Copyright (C) 2025, Rafael Caricio <rafael@caricio.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License, v2.0.
+// If a copy of the MPL was not distributed with this file, You can obtain one at
+// <https://mozilla.org/MPL/2.0/>.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+use gst::glib;
+use gst::prelude::*;
+use gst::subclass::prelude::*;
+use gst_base::subclass::prelude::*;
+
+mod imp;
+
+#[derive(Debug, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Clone, Copy, glib::Enum)]
+#[repr(u32)]
+#[enum_type(name = "GstSubprocessPipeCapsChange")]
+pub enum CapsChange {
+    #[default]
+    #[enum_value(name = "Ignore: Keep the subprocess running", nick = "ignore")]
+    Ignore,
+    #[enum_value(
+        name = "Restart: Restart the subprocess with the command templated for the new caps",
+        nick = "restart"
+    )]
+    Restart,
+    #[enum_value(name = "Error: Fail negotiation", nick = "error")]
+    Error,
+}
+
+#[derive(Debug, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Clone, Copy, glib::Enum)]
+#[repr(u32)]
+#[enum_type(name = "GstSubprocessPipeRestartPolicy")]
+pub enum RestartPolicy {
+    #[default]
+    #[enum_value(name = "Never: Fail when the subprocess exits", nick = "never")]
+    Never,
+    #[enum_value(
+        name = "On failure: Restart the subprocess if it exits with an error",
+        nick = "on-failure"
+    )]
+    OnFailure,
+    #[enum_value(name = "Always: Restart the subprocess whenever it exits", nick = "always")]
+    Always,
+}
+
+#[derive(Debug, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Clone, Copy, glib::Enum)]
+#[repr(u32)]
+#[enum_type(name = "GstSubprocessPipeStopSignal")]
+pub enum StopSignal {
+    #[enum_value(name = "SIGHUP", nick = "sighup")]
+    Hup,
+    #[enum_value(name = "SIGINT", nick = "sigint")]
+    Int,
+    #[enum_value(name = "SIGQUIT", nick = "sigquit")]
+    Quit,
+    #[default]
+    #[enum_value(name = "SIGTERM", nick = "sigterm")]
+    Term,
+    #[enum_value(name = "SIGUSR1", nick = "sigusr1")]
+    Usr1,
+    #[enum_value(name = "SIGUSR2", nick = "sigusr2")]
+    Usr2,
+}
+
+#[derive(Debug, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Clone, Copy, glib::Enum)]
+#[repr(u32)]
+#[enum_type(name = "GstSubprocessPipeStallAction")]
+pub enum StallAction {
+    #[enum_value(
+        name = "Drop: Drop the buffer, unless it was already partially written",
+        nick = "drop"
+    )]
+    Drop,
+    #[default]
+    #[enum_value(name = "Warn: Post a warning and keep waiting", nick = "warn")]
+    Warn,
+    #[enum_value(name = "Error: Fail with an error", nick = "error")]
+    Error,
+}
+
+impl StopSignal {
+    pub fn as_raw(self) -> libc::c_int {
+        match self {
+            StopSignal::Hup => libc::SIGHUP,
+            StopSignal::Int => libc::SIGINT,
+            StopSignal::Quit => libc::SIGQUIT,
+            StopSignal::Term => libc::SIGTERM,
+            StopSignal::Usr1 => libc::SIGUSR1,
+            StopSignal::Usr2 => libc::SIGUSR2,
+        }
+    }
+}
+
+glib::wrapper! {
+    pub struct PipeSink(ObjectSubclass<imp::PipeSink>) @extends gst_base::BaseSink, gst::Element, gst::Object;
+}
+
+/// Trait for the elements built on the subprocess machinery of [`PipeSink`].
+pub trait PipeSinkImpl: BaseSinkImpl {}
+
+unsafe impl<T: PipeSinkImpl> IsSubclassable<T> for PipeSink {}
+
+glib::wrapper! {
+    pub struct VideoPipeSink(ObjectSubclass<imp::VideoPipeSink>) @extends PipeSink, gst_base::BaseSink, gst::Element, gst::Object;
+}
+
+glib::wrapper! {
+    pub struct AudioPipeSink(ObjectSubclass<imp::AudioPipeSink>) @extends PipeSink, gst_base::BaseSink, gst::Element, gst::Object;
+}
+
+pub fn register(plugin: &gst::Plugin) -> Result<(), glib::BoolError> {
+    #[cfg(feature = "doc")]
+    {
+        PipeSink::static_type().mark_as_plugin_api(gst::PluginAPIFlags::empty());
+        CapsChange::static_type().mark_as_plugin_api(gst::PluginAPIFlags::empty());
+        RestartPolicy::static_type().mark_as_plugin_api(gst::PluginAPIFlags::empty());
+        StopSignal::static_type().mark_as_plugin_api(gst::PluginAPIFlags::empty());
+        StallAction::static_type().mark_as_plugin_api(gst::PluginAPIFlags::empty());
+        crate::queue::Leaky::static_type().mark_as_plugin_api(gst::PluginAPIFlags::empty());
+        crate::framing::Framing::static_type().mark_as_plugin_api(gst::PluginAPIFlags::empty());
+        crate::transport::Transport::static_type().mark_as_plugin_api(gst::PluginAPIFlags::empty());
+    }
+
+    gst::Element::register(
+        Some(plugin),
+        "videopipesink",
+        gst::Rank::NONE,
+        VideoPipeSink::static_type(),
+    )?;
+
+    gst::Element::register(
+        Some(plugin),
+        "audiopipesink",
+        gst::Rank::NONE,
+        AudioPipeSink::static_type(),
+    )
+}
+
+// Function used for direct element registration during testing
+pub fn register_element() -> Result<(), glib::BoolError> {
+    gst::Element::register(
+        None,
+        "videopipesink",
+        gst::Rank::NONE,
+        VideoPipeSink::static_type(),
+    )?;
+
+    gst::Element::register(
+        None,
+        "audiopipesink",
+        gst::Rank::NONE,
+        AudioPipeSink::static_type(),
+    )
+}
@@ -0,0 +1,709 @@
+// Copyright (C) 2025, Rafael Caricio <rafael@caricio.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License, v2.0.
+// If a copy of the MPL was not distributed with this file, You can obtain one at
+// <https://mozilla.org/MPL/2.0/>.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+use gst::glib;
+use gst::prelude::*;
+use gst::subclass::prelude::*;
+use gst_base::prelude::*;
+use gst_base::subclass::base_src::CreateSuccess;
+use gst_base::subclass::prelude::*;
+use once_cell::sync::Lazy;
+use std::io::{self, BufRead, BufReader, Read};
+use std::process::{Child, ChildStdout, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::command::{terminate, wait_timeout, CommandSettings};
+use crate::framing::{self, Framing};
+use crate::length_prefixed::{self, RecordKind};
+use crate::pack;
+use crate::pipesink::StopSignal;
+use crate::template::Variables;
+use crate::writer::{read_up_to, InterruptibleReader, Waker};
+
+static CAT: Lazy<gst::DebugCategory> = Lazy::new(|| {
+    gst::DebugCategory::new(
+        "pipesrc",
+        gst::DebugColorFlags::empty(),
+        Some("Subprocess Pipe Source Element"),
+    )
+});
+
+static KILL_TIMEOUT_DEFAULT: gst::ClockTime = gst::ClockTime::from_seconds(5);
+// Time the subprocess gets to exit on its own after closing stdout or after we closed it
+static EXIT_TIMEOUT: gst::ClockTime = gst::ClockTime::from_mseconds(100);
+// Upper bound for Y4M header lines, which are short text
+const Y4M_MAX_LINE: u64 = 4096;
+
+type StdoutReader = BufReader<InterruptibleReader<ChildStdout>>;
+
+// Plugin state
+#[derive(Default)]
+struct State {
+    child_process: Option<Child>,
+    reader: Option<StdoutReader>,
+    stderr_thread: Option<thread::JoinHandle<()>>,
+    // Configured for raw framing, read from the stream header otherwise
+    caps: Option<gst::Caps>,
+    // Set for raw video caps
+    video_info: Option<gst_video::VideoInfo>,
+    // Set for raw audio caps
+    audio_info: Option<gst_audio::AudioInfo>,
+    // Frames or samples produced so far, for timestamping
+    position: u64,
+}
+
+impl State {
+    fn set_caps(&mut self, caps: gst::Caps) {
+        self.video_info = gst_video::VideoInfo::from_caps(&caps).ok();
+        self.audio_info = gst_audio::AudioInfo::from_caps(&caps).ok();
+        self.caps = Some(caps);
+        self.position = 0;
+    }
+
+    // Frames or samples per second of the current caps, as a fraction
+    fn rate(&self) -> Option<(u64, u64)> {
+        if let Some(info) = &self.video_info {
+            let fps = info.fps();
+            return (fps.numer() > 0).then(|| (fps.numer() as u64, fps.denom() as u64));
+        }
+
+        self.audio_info.as_ref().map(|info| (info.rate() as u64, 1))
+    }
+
+    // Timestamp a buffer of `units` frames or samples following the previous one
+    fn timestamp(&mut self, buffer: &mut gst::BufferRef, units: u64) {
+        let start = self.position;
+        self.position += units;
+
+        let Some((numer, denom)) = self.rate() else {
+            return;
+        };
+        let time = |position: u64| {
+            (position * denom)
+                .mul_div_floor(gst::ClockTime::SECOND.nseconds(), numer)
+                .map(gst::ClockTime::from_nseconds)
+        };
+
+        let pts = time(start);
+        buffer.set_pts(pts);
+        buffer.set_duration(time(self.position).zip(pts).map(|(end, start)| end - start));
+        buffer.set_offset(start);
+        buffer.set_offset_end(self.position);
+    }
+}
+
+// Properties
+#[derive(Debug, Clone)]
+struct Settings {
+    command: CommandSettings,
+    caps: Option<gst::Caps>,
+    framing: Framing,
+    stop_signal: StopSignal,
+    kill_timeout: gst::ClockTime,
+    error_on_nonzero_exit: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            command: CommandSettings::default(),
+            caps: None,
+            framing: Framing::default(),
+            stop_signal: StopSignal::default(),
+            kill_timeout: KILL_TIMEOUT_DEFAULT,
+            error_on_nonzero_exit: false,
+        }
+    }
+}
+
+pub struct PipeSrc {
+    settings: Mutex<Settings>,
+    state: Mutex<State>,
+    // Outside of the state so that unlock() doesn't wait for a blocked create()
+    waker: Arc<Waker>,
+}
+
+impl Default for PipeSrc {
+    fn default() -> Self {
+        Self {
+            settings: Mutex::new(Settings::default()),
+            state: Mutex::new(State::default()),
+            waker: Arc::new(Waker::new().expect("Failed to create wakeup pipe")),
+        }
+    }
+}
+
+#[glib::object_subclass]
+impl ObjectSubclass for PipeSrc {
+    const NAME: &'static str = "PipeSrc";
+    type Type = super::PipeSrc;
+    type ParentType = gst_base::PushSrc;
+}
+
+impl ObjectImpl for PipeSrc {
+    fn properties() -> &'static [glib::ParamSpec] {
+        static PROPERTIES: Lazy<Vec<glib::ParamSpec>> = Lazy::new(|| {
+            let mut properties = CommandSettings::properties();
+            properties.extend([
+                glib::ParamSpecBoxed::builder::<gst::Caps>("caps")
+                    .nick("Caps")
+                    .blurb("Caps of the subprocess output, required for raw framing")
+                    .mutable_ready()
+                    .build(),
+                glib::ParamSpecEnum::builder_with_default("framing", Framing::default())
+                    .nick("Framing")
+                    .blurb("How buffers are delimited in the subprocess output")
+                    .mutable_ready()
+                    .build(),
+                glib::ParamSpecEnum::builder_with_default("stop-signal", StopSignal::default())
+                    .nick("Stop signal")
+                    .blurb("Signal sent to the subprocess if it doesn't exit after its stdout is closed")
+                    .mutable_playing()
+                    .build(),
+                glib::ParamSpecUInt64::builder("kill-timeout")
+                    .nick("Kill timeout")
+                    .blurb("Wait time in nanoseconds after the stop signal before sending SIGKILL")
+                    .default_value(KILL_TIMEOUT_DEFAULT.nseconds())
+                    .mutable_playing()
+                    .build(),
+                glib::ParamSpecBoolean::builder("error-on-nonzero-exit")
+                    .nick("Error on non-zero exit")
+                    .blurb("Post an error instead of EOS when the subprocess exits with a non-zero code")
+                    .default_value(false)
+                    .mutable_ready()
+                    .build(),
+            ]);
+            properties
+        });
+
+        PROPERTIES.as_ref()
+    }
+
+    fn set_property(&self, _id: usize, value: &glib::Value, pspec: &glib::ParamSpec) {
+        let mut settings = self.settings.lock().unwrap();
+        if settings.command.set_property(pspec.name(), value) {
+            return;
+        }
+
+        match pspec.name() {
+            "caps" => {
+                settings.caps = value.get().expect("type checked upstream");
+            }
+            "framing" => {
+                settings.framing = value.get().expect("type checked upstream");
+            }
+            "stop-signal" => {
+                settings.stop_signal = value.get().expect("type checked upstream");
+            }
+            "kill-timeout" => {
+                settings.kill_timeout = value.get().expect("type checked upstream");
+            }
+            "error-on-nonzero-exit" => {
+                settings.error_on_nonzero_exit = value.get().expect("type checked upstream");
+            }
+            _ => unimplemented!(),
+        }
+    }
+
+    fn property(&self, _id: usize, pspec: &glib::ParamSpec) -> glib::Value {
+        let settings = self.settings.lock().unwrap();
+        if let Some(value) = settings.command.property(pspec.name()) {
+            return value;
+        }
+
+        match pspec.name() {
+            "caps" => {
+                settings.caps.to_value()
+            }
+            "framing" => {
+                settings.framing.to_value()
+            }
+            "stop-signal" => {
+                settings.stop_signal.to_value()
+            }
+            "kill-timeout" => {
+                settings.kill_timeout.to_value()
+            }
+            "error-on-nonzero-exit" => {
+                settings.error_on_nonzero_exit.to_value()
+            }
+            _ => unimplemented!(),
+        }
+    }
+
+    fn constructed(&self) {
+        self.parent_constructed();
+        self.obj().set_format(gst::Format::Time);
+    }
+}
+
+impl GstObjectImpl for PipeSrc {}
+
+impl ElementImpl for PipeSrc {
+    fn metadata() -> Option<&'static gst::subclass::ElementMetadata> {
+        static ELEMENT_METADATA: Lazy<gst::subclass::ElementMetadata> = Lazy::new(|| {
+            gst::subclass::ElementMetadata::new(
+                "Pipe Source",
+                "Source",
+                "Reads buffers from the output of a provided subprocess",
+                "Rafael Caricio <rafael@caricio.com>",
+            )
+        });
+
+        Some(&*ELEMENT_METADATA)
+    }
+
+    fn pad_templates() -> &'static [gst::PadTemplate] {
+        static PAD_TEMPLATES: Lazy<Vec<gst::PadTemplate>> = Lazy::new(|| {
+            let src_pad_template = gst::PadTemplate::new(
+                "src",
+                gst::PadDirection::Src,
+                gst::PadPresence::Always,
+                &gst::Caps::new_any(),
+            )
+            .unwrap();
+
+            vec![src_pad_template]
+        });
+
+        PAD_TEMPLATES.as_ref()
+    }
+}
+
+impl PipeSrc {
+    // Spawn the subprocess with its stdout piped into a reader that unlock() can interrupt
+    fn spawn_child(&self, state: &mut State, settings: &Settings) -> Result<(), gst::ErrorMessage> {
+        let vars = match &state.caps {
+            Some(caps) => Variables::from_caps(caps).map_err(|err| {
+                gst::error_msg!(
+                    gst::ResourceError::Settings,
+                    ["Failed to parse caps {}: {}", caps, err]
+                )
+            })?,
+            None => Variables::default(),
+        };
+
+        let (mut command, command_line) = settings.command.command(&vars)?;
+        let current_dir = settings.command.working_directory()?;
+
+        gst::info!(CAT, imp = self, "Starting subprocess with command: {}", command_line);
+
+        let mut child = command
+            .current_dir(current_dir)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| {
+                gst::error_msg!(
+                    gst::ResourceError::Failed,
+                    ["Failed to start process: {}", e]
+                )
+            })?;
+
+        let pid = child.id();
+
+        let stdout = child.stdout.take().unwrap();
+        let reader = match InterruptibleReader::new(stdout, self.waker.clone()) {
+            Ok(reader) => reader,
+            Err(err) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(gst::error_msg!(
+                    gst::ResourceError::Failed,
+                    ["Failed to make stdout non-blocking: {}", err]
+                ));
+            }
+        };
+
+        // Setup stderr monitoring
+        let stderr = child.stderr.take().unwrap();
+
+        let stderr_thread = thread::spawn({
+            let this = self.downgrade();
+            move || {
+                let reader = BufReader::new(stderr);
+                for line in reader.lines().map_while(Result::ok) {
+                    let this = match this.upgrade() {
+                        Some(this) => this,
+                        None => return,
+                    };
+                    gst::warning!(CAT, imp = this, "stderr: {}", line);
+                }
+            }
+        });
+
+        state.child_process = Some(child);
+        state.reader = Some(BufReader::new(reader));
+        state.stderr_thread = Some(stderr_thread);
+
+        gst::info!(CAT, imp = self, "Started subprocess with PID: {}", pid);
+        Ok(())
+    }
+
+    // Close stdout, reap the subprocess and join the stderr monitoring thread.
+    //
+    // Closing stdout makes the next write of the subprocess fail, it then gets a short time to
+    // exit on its own, `kill-timeout` after receiving `stop-signal`, and is finally killed.
+    fn stop_child(&self, state: &mut State, settings: &Settings) {
+        drop(state.reader.take());
+
+        if let Some(mut child) = state.child_process.take() {
+            let pid = child.id();
+
+            match terminate(&mut child, EXIT_TIMEOUT, settings.stop_signal.as_raw(), settings.kill_timeout) {
+                Ok(status) => {
+                    gst::info!(CAT, imp = self, "Process (PID: {}) exited with {}", pid, status);
+                }
+                Err(err) => {
+                    gst::warning!(CAT, imp = self, "Failed to stop child process (PID: {}): {}", pid, err);
+                }
+            }
+        }
+
+        if let Some(thread) = state.stderr_thread.take() {
+            thread.join().unwrap();
+        }
+    }
+
+    // Called when the subprocess closed its stdout, ends the stream with EOS unless it failed
+    fn handle_eof(&self, state: &mut State) -> gst::FlowError {
+        let error_on_nonzero_exit = self.settings.lock().unwrap().error_on_nonzero_exit;
+        let Some(child) = state.child_process.as_mut() else {
+            return gst::FlowError::Eos;
+        };
+        let pid = child.id();
+
+        match wait_timeout(child, EXIT_TIMEOUT) {
+            Ok(Some(status)) => {
+                gst::info!(CAT, imp = self, "Process (PID: {}) exited with {}", pid, status);
+                if let Some(code) = status.code().filter(|code| *code != 0) {
+                    if error_on_nonzero_exit {
+                        gst::element_imp_error!(
+                            self,
+                            gst::ResourceError::Failed,
+                            ["Subprocess exited with code {}", code]
+                        );
+                        return gst::FlowError::Error;
+                    }
+                }
+            }
+            Ok(None) => {
+                gst::debug!(CAT, imp = self, "Process (PID: {}) closed its stdout but is still running", pid);
+            }
+            Err(err) => {
+                gst::warning!(CAT, imp = self, "Failed to wait for child process (PID: {}): {}", pid, err);
+            }
+        }
+
+        gst::debug!(CAT, imp = self, "End of subprocess output");
+        gst::FlowError::Eos
+    }
+
+    // Update the caps from the stream and push them downstream
+    fn update_caps(&self, state: &mut State, caps: gst::Caps) -> Result<(), gst::FlowError> {
+        gst::debug!(CAT, imp = self, "Caps from the stream: {}", caps);
+        if state.caps.as_ref() == Some(&caps) {
+            return Ok(());
+        }
+
+        self.obj().set_caps(&caps).map_err(|_| {
+            gst::element_imp_error!(
+                self,
+                gst::CoreError::Negotiation,
+                ["Failed to negotiate caps {}", caps]
+            );
+            gst::FlowError::NotNegotiated
+        })?;
+
+        state.set_caps(caps);
+        Ok(())
+    }
+
+    // Read a tightly packed frame, or `None` at the end of the stream
+    fn read_video_frame(&self, state: &mut State, info: &gst_video::VideoInfo) -> io::Result<Option<gst::Buffer>> {
+        let size = pack::packed_size(info);
+        let data = read_up_to(state.reader.as_mut().unwrap(), size)?;
+
+        if data.len() < size {
+            if !data.is_empty() {
+                gst::warning!(CAT, imp = self, "Discarding incomplete frame of {} bytes", data.len());
+            }
+            return Ok(None);
+        }
+
+        let mut buffer = pack::unpack_planes(data, info).map_err(io::Error::other)?;
+        state.timestamp(buffer.get_mut().unwrap(), 1);
+        Ok(Some(buffer))
+    }
+
+    fn read_raw(&self, state: &mut State, blocksize: usize) -> io::Result<Option<gst::Buffer>> {
+        if let Some(info) = state.video_info.clone() {
+            return self.read_video_frame(state, &info);
+        }
+
+        let reader = state.reader.as_mut().unwrap();
+
+        if let Some(bpf) = state.audio_info.as_ref().map(|info| info.bpf() as usize) {
+            // Only whole audio frames, one sample of every channel
+            let mut data = read_up_to(reader, (blocksize / bpf).max(1) * bpf)?;
+            data.truncate(data.len() / bpf * bpf);
+            if data.is_empty() {
+                return Ok(None);
+            }
+
+            let samples = (data.len() / bpf) as u64;
+            let mut buffer = gst::Buffer::from_mut_slice(data);
+            state.timestamp(buffer.get_mut().unwrap(), samples);
+            return Ok(Some(buffer));
+        }
+
+        // Any other caps, pass on whatever the subprocess wrote so far
+        let mut data = vec![0u8; blocksize.max(1)];
+        let n = loop {
+            match reader.read(&mut data) {
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => (),
+                result => break result?,
+            }
+        };
+        if n == 0 {
+            return Ok(None);
+        }
+
+        data.truncate(n);
+        Ok(Some(gst::Buffer::from_mut_slice(data)))
+    }
+
+    fn read_y4m(&self, state: &mut State) -> Result<Option<gst::Buffer>, gst::FlowError> {
+        let read_line = |state: &mut State| -> io::Result<String> {
+            let mut line = Vec::new();
+            state.reader.as_mut().unwrap().take(Y4M_MAX_LINE).read_until(b'\n', &mut line)?;
+            String::from_utf8(line).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+        };
+
+        if state.caps.is_none() {
+            let line = read_line(state).map_err(|err| self.read_error(err))?;
+            if line.is_empty() {
+                return Ok(None);
+            }
+
+            let info = framing::parse_y4m_header(&line).map_err(|err| {
+                gst::element_imp_error!(self, gst::StreamError::Decode, ["{}", err]);
+                gst::FlowError::Error
+            })?;
+            self.update_caps(state, info.to_caps().unwrap())?;
+        }
+
+        let line = read_line(state).map_err(|err| self.read_error(err))?;
+        if line.is_empty() {
+            return Ok(None);
+        }
+        if !line.as_bytes().starts_with(&framing::Y4M_FRAME_HEADER[..5]) {
+            gst::element_imp_error!(self, gst::StreamError::Decode, ["Invalid Y4M frame header {:?}", line]);
+            return Err(gst::FlowError::Error);
+        }
+
+        let info = state.video_info.clone().unwrap();
+        self.read_video_frame(state, &info).map_err(|err| self.read_error(err))
+    }
+
+    fn read_length_prefixed(&self, state: &mut State) -> Result<Option<gst::Buffer>, gst::FlowError> {
+        loop {
+            let mut reader = length_prefixed::Reader::new(state.reader.as_mut().unwrap());
+            let Some(record) = reader.read_record().map_err(|err| self.read_error(err))? else {
+                return Ok(None);
+            };
+
+            match record.header.kind {
+                RecordKind::Caps => {
+                    let caps = record
+                        .caps()
+                        .and_then(|caps| caps.parse::<gst::Caps>().ok())
+                        .ok_or_else(|| {
+                            gst::element_imp_error!(self, gst::StreamError::Decode, ["Invalid caps record"]);
+                            gst::FlowError::Error
+                        })?;
+                    self.update_caps(state, caps)?;
+                }
+                RecordKind::Buffer => {
+                    if state.caps.is_none() {
+                        gst::element_imp_error!(self, gst::StreamError::Decode, ["Buffer record before caps record"]);
+                        return Err(gst::FlowError::Error);
+                    }
+
+                    let mut buffer = match &state.video_info {
+                        Some(info) => pack::unpack_planes(record.payload, info).map_err(|err| {
+                            gst::element_imp_error!(self, gst::StreamError::Decode, ["{}", err]);
+                            gst::FlowError::Error
+                        })?,
+                        None => gst::Buffer::from_mut_slice(record.payload),
+                    };
+
+                    let header = record.header;
+                    let buffer_ref = buffer.get_mut().unwrap();
+                    buffer_ref.set_pts(header.pts);
+                    buffer_ref.set_dts(header.dts);
+                    buffer_ref.set_duration(header.duration);
+                    buffer_ref.set_flags(header.flags);
+                    return Ok(Some(buffer));
+                }
+                RecordKind::Slot => {
+                    gst::element_imp_error!(
+                        self,
+                        gst::StreamError::Decode,
+                        ["Shared memory slot records are not supported"]
+                    );
+                    return Err(gst::FlowError::Error);
+                }
+            }
+        }
+    }
+
+    // Map a failed read to the flow return, interrupted reads are flushing
+    fn read_error(&self, err: io::Error) -> gst::FlowError {
+        if self.waker.is_flushing() {
+            gst::debug!(CAT, imp = self, "Flushing, read interrupted");
+            return gst::FlowError::Flushing;
+        }
+
+        gst::element_imp_error!(
+            self,
+            gst::ResourceError::Read,
+            ["Failed to read from process stdout: {}", err]
+        );
+        gst::FlowError::Error
+    }
+}
+
+impl BaseSrcImpl for PipeSrc {
+    fn start(&self) -> Result<(), gst::ErrorMessage> {
+        let settings = self.settings.lock().unwrap().clone();
+        let mut state = self.state.lock().unwrap();
+
+        settings.command.validate().inspect_err(|err| {
+            gst::debug!(CAT, imp = self, "Invalid command settings: {}", err);
+        })?;
+
+        *state = State::default();
+
+        match (settings.framing, &settings.caps) {
+            (Framing::Wav, _) => {
+                return Err(gst::error_msg!(
+                    gst::ResourceError::Settings,
+                    ["WAV framing is not supported for reading"]
+                ));
+            }
+            (Framing::Raw, None) => {
+                return Err(gst::error_msg!(
+                    gst::ResourceError::Settings,
+                    ["Raw framing requires the caps property"]
+                ));
+            }
+            (Framing::Raw, Some(caps)) if !caps.is_fixed() => {
+                return Err(gst::error_msg!(
+                    gst::ResourceError::Settings,
+                    ["Caps {} are not fixed", caps]
+                ));
+            }
+            // Caps of other framings come with the stream
+            (Framing::Raw, Some(caps)) => state.set_caps(caps.clone()),
+            _ => (),
+        }
+
+        self.waker.reset();
+        self.spawn_child(&mut state, &settings)?;
+
+        gst::info!(CAT, imp = self, "Started");
+        Ok(())
+    }
+
+    fn stop(&self) -> Result<(), gst::ErrorMessage> {
+        let settings = self.settings.lock().unwrap().clone();
+        let mut state = self.state.lock().unwrap();
+
+        self.stop_child(&mut state, &settings);
+        *state = State::default();
+
+        gst::info!(CAT, imp = self, "Stopped");
+        Ok(())
+    }
+
+    fn is_seekable(&self) -> bool {
+        false
+    }
+
+    fn caps(&self, filter: Option<&gst::Caps>) -> Option<gst::Caps> {
+        // Not from the state, which is locked while create() waits for the subprocess
+        let mut caps = self
+            .obj()
+            .src_pad()
+            .current_caps()
+            .or_else(|| self.settings.lock().unwrap().caps.clone())
+            .unwrap_or_else(|| self.obj().src_pad().pad_template_caps());
+
+        if let Some(filter) = filter {
+            caps = filter.intersect_with_mode(&caps, gst::CapsIntersectMode::First);
+        }
+
+        gst::log!(CAT, imp = self, "Returning caps {}", caps);
+        Some(caps)
+    }
+
+    fn negotiate(&self) -> Result<(), gst::LoggableError> {
+        // Y4M and length-prefixed streams set their caps once the stream header was read
+        let caps = self.state.lock().unwrap().caps.clone();
+        match caps {
+            Some(caps) => self
+                .obj()
+                .set_caps(&caps)
+                .map_err(|_| gst::loggable_error!(CAT, "Failed to negotiate caps {}", caps)),
+            None => Ok(()),
+        }
+    }
+
+    fn unlock(&self) -> Result<(), gst::ErrorMessage> {
+        gst::debug!(CAT, imp = self, "Unlocking");
+        self.waker.wake();
+        Ok(())
+    }
+
+    fn unlock_stop(&self) -> Result<(), gst::ErrorMessage> {
+        gst::debug!(CAT, imp = self, "Unlock stopped");
+        self.waker.reset();
+        Ok(())
+    }
+}
+
+impl PushSrcImpl for PipeSrc {
+    fn create(&self, _buffer: Option<&mut gst::BufferRef>) -> Result<CreateSuccess, gst::FlowError> {
+        let framing = self.settings.lock().unwrap().framing;
+        let blocksize = self.obj().blocksize() as usize;
+        let mut state = self.state.lock().unwrap();
+
+        if state.reader.is_none() {
+            gst::error!(CAT, imp = self, "Child process not started");
+            return Err(gst::FlowError::Error);
+        }
+
+        let buffer = match framing {
+            Framing::Raw => self.read_raw(&mut state, blocksize).map_err(|err| self.read_error(err))?,
+            Framing::Y4m => self.read_y4m(&mut state)?,
+            Framing::LengthPrefixed => self.read_length_prefixed(&mut state)?,
+            Framing::Wav => unreachable!(),
+        };
+
+        match buffer {
+            Some(buffer) => {
+                gst::trace!(CAT, imp = self, "Read buffer of size {}", buffer.size());
+                Ok(CreateSuccess::NewBuffer(buffer))
+            }
+            None => Err(self.handle_eof(&mut state)),
+        }
+    }
+}
@@ -0,0 +1,35 @@
+// Copyright (C) 2025, Rafael Caricio <rafael@caricio.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License, v2.0.
+// If a copy of the MPL was not distributed with this file, You can obtain one at
+// <https://mozilla.org/MPL/2.0/>.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+use gst::glib;
+use gst::prelude::*;
+
+mod imp;
+
+glib::wrapper! {
+    pub struct PipeSrc(ObjectSubclass<imp::PipeSrc>) @extends gst_base::PushSrc, gst_base::BaseSrc, gst::Element, gst::Object;
+}
+
+pub fn register(plugin: &gst::Plugin) -> Result<(), glib::BoolError> {
+    gst::Element::register(
+        Some(plugin),
+        "pipesrc",
+        gst::Rank::NONE,
+        PipeSrc::static_type(),
+    )
+}
+
+// Function used for direct element registration during testing
+pub fn register_element() -> Result<(), glib::BoolError> {
+    gst::Element::register(
+        None,
+        "pipesrc",
+        gst::Rank::NONE,
+        PipeSrc::static_type(),
+    )
+}
@@ -0,0 +1,249 @@
+// Copyright (C) 2025, Rafael Caricio <rafael@caricio.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License, v2.0.
+// If a copy of the MPL was not distributed with this file, You can obtain one at
+// <https://mozilla.org/MPL/2.0/>.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Bounded buffer queue between the streaming thread and a writer thread.
+
+use gst::glib;
+use std::collections::VecDeque;
+use std::io;
+use std::sync::{Condvar, Mutex};
+use std::time::Duration;
+
+use crate::writer::Waker;
+
+#[derive(Debug, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Clone, Copy, glib::Enum)]
+#[repr(u32)]
+#[enum_type(name = "GstSubprocessPipeLeaky")]
+pub enum Leaky {
+    #[default]
+    #[enum_value(name = "None: Block until there is room in the queue", nick = "none")]
+    None,
+    #[enum_value(name = "Upstream: Drop new buffers when the queue is full", nick = "upstream")]
+    Upstream,
+    #[enum_value(name = "Downstream: Drop the oldest buffers when the queue is full", nick = "downstream")]
+    Downstream,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    // 0 means unlimited for both
+    pub max_buffers: u32,
+    pub max_bytes: u64,
+    pub leaky: Leaky,
+}
+
+impl Limits {
+    pub fn is_enabled(&self) -> bool {
+        self.max_buffers > 0 || self.max_bytes > 0
+    }
+}
+
+#[derive(Debug)]
+pub enum PushError {
+    Flushing,
+    // The writer stopped, after a write error or a shutdown
+    Closed,
+}
+
+// Stream header written before a buffer, e.g. after a caps change
+type Header = Option<Vec<u8>>;
+
+#[derive(Debug, Default)]
+struct Inner {
+    buffers: VecDeque<(Header, gst::Buffer)>,
+    // Headers of dropped buffers, written before the next buffer pushed
+    header: Header,
+    bytes: u64,
+    flushing: bool,
+    finishing: bool,
+    closed: bool,
+}
+
+impl Inner {
+    fn is_full(&self, limits: &Limits, size: u64) -> bool {
+        // Always accept a buffer into an empty queue, even if it's bigger than max-bytes
+        !self.buffers.is_empty()
+            && ((limits.max_buffers > 0 && self.buffers.len() >= limits.max_buffers as usize)
+                || (limits.max_bytes > 0 && self.bytes + size > limits.max_bytes))
+    }
+
+    fn pop_front(&mut self) -> Option<(Header, gst::Buffer)> {
+        let (header, buffer) = self.buffers.pop_front()?;
+        self.bytes -= buffer.size() as u64;
+        Some((header, buffer))
+    }
+
+    // Drop the oldest buffer, its header goes to the buffer after it
+    fn drop_front(&mut self) {
+        let Some((header, _)) = self.pop_front() else {
+            return;
+        };
+
+        match self.buffers.front_mut() {
+            Some((next, _)) => *next = concat(header, next.take()),
+            None => self.header = concat(self.header.take(), header),
+        }
+    }
+
+    // Drop all buffers, keeping their headers for the next buffer
+    fn clear(&mut self) {
+        for (header, _) in self.buffers.drain(..) {
+            self.header = concat(self.header.take(), header);
+        }
+        self.bytes = 0;
+    }
+}
+
+fn concat(first: Header, second: Header) -> Header {
+    match (first, second) {
+        (Some(mut first), Some(second)) => {
+            first.extend(second);
+            Some(first)
+        }
+        (first, second) => first.or(second),
+    }
+}
+
+/// Buffers waiting to be written by a writer thread.
+///
+/// The streaming thread pushes with [`FrameQueue::push`], the writer thread takes buffers with
+/// [`FrameQueue::pop`] until the queue is finished or shut down.
+#[derive(Debug)]
+pub struct FrameQueue {
+    limits: Limits,
+    inner: Mutex<Inner>,
+    cond: Condvar,
+    // Interrupts the writer thread when it's blocked on the pipe
+    waker: Waker,
+}
+
+impl FrameQueue {
+    pub fn new(limits: Limits, flushing: bool) -> io::Result<Self> {
+        Ok(FrameQueue {
+            limits,
+            inner: Mutex::new(Inner {
+                flushing,
+                ..Default::default()
+            }),
+            cond: Condvar::new(),
+            waker: Waker::new()?,
+        })
+    }
+
+    pub fn waker(&self) -> &Waker {
+        &self.waker
+    }
+
+    /// Queues a buffer, to be written after `header`, applying the leaky mode if the queue is
+    /// full. Headers are never dropped: the header of a dropped buffer is written before the
+    /// buffer that follows it.
+    ///
+    /// Returns the number of buffers dropped to make room, or 1 if `buffer` itself was dropped.
+    pub fn push(&self, buffer: gst::Buffer, header: Header) -> Result<u32, PushError> {
+        let size = buffer.size() as u64;
+        let mut inner = self.inner.lock().unwrap();
+        let mut dropped = 0;
+
+        loop {
+            if inner.flushing {
+                return Err(PushError::Flushing);
+            }
+            if inner.closed {
+                return Err(PushError::Closed);
+            }
+            if !inner.is_full(&self.limits, size) {
+                break;
+            }
+
+            match self.limits.leaky {
+                Leaky::None => inner = self.cond.wait(inner).unwrap(),
+                Leaky::Upstream => {
+                    inner.header = concat(inner.header.take(), header);
+                    return Ok(1);
+                }
+                Leaky::Downstream => {
+                    inner.drop_front();
+                    dropped += 1;
+                }
+            }
+        }
+
+        let header = concat(inner.header.take(), header);
+        inner.bytes += size;
+        inner.buffers.push_back((header, buffer));
+        self.cond.notify_all();
+
+        Ok(dropped)
+    }
+
+    /// Waits for the next buffer to write, with the header to write before it. Returns `None`
+    /// once the writer should stop.
+    pub fn pop(&self) -> Option<(Header, gst::Buffer)> {
+        let mut inner = self.inner.lock().unwrap();
+
+        loop {
+            if inner.closed {
+                return None;
+            }
+            if let Some(item) = inner.pop_front() {
+                self.cond.notify_all();
+                return Some(item);
+            }
+            if inner.finishing {
+                return None;
+            }
+
+            inner = self.cond.wait(inner).unwrap();
+        }
+    }
+
+    /// Discards all queued buffers while flushing, and fails pushes until unset. Their headers
+    /// are kept for the next buffer.
+    pub fn set_flushing(&self, flushing: bool) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.flushing = flushing;
+        if flushing {
+            inner.clear();
+        }
+        self.cond.notify_all();
+    }
+
+    /// Lets the writer write the remaining buffers and stop.
+    pub fn finish(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.finishing = true;
+        self.cond.notify_all();
+    }
+
+    /// Discards all queued buffers and interrupts the writer.
+    pub fn shutdown(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.clear();
+        inner.closed = true;
+        self.cond.notify_all();
+        self.waker.wake();
+    }
+
+    /// Called by the writer when it stops.
+    pub fn close(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.closed = true;
+        self.cond.notify_all();
+    }
+
+    /// Waits for the writer to stop after [`FrameQueue::finish`]. Returns false on timeout, or
+    /// when interrupted by flushing.
+    pub fn wait_closed(&self, timeout: Duration) -> bool {
+        let inner = self.inner.lock().unwrap();
+        let (inner, _) = self
+            .cond
+            .wait_timeout_while(inner, timeout, |inner| !inner.closed && !inner.flushing)
+            .unwrap();
+        inner.closed
+    }
+}
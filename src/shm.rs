@@ -0,0 +1,359 @@
+// Copyright (C) 2025, Rafael Caricio <rafael@caricio.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License, v2.0.
+// If a copy of the MPL was not distributed with this file, You can obtain one at
+// <https://mozilla.org/MPL/2.0/>.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Shared memory ring buffer written with `transport=shm`, and a reader for it.
+//!
+//! The ring is a memfd inherited by the subprocess, with its file descriptor number in the
+//! `{shm_fd}` placeholder. It starts with a header, all fields little endian:
+//!
+//! | Offset | Size      | Field                                   |
+//! |--------|-----------|-----------------------------------------|
+//! | 0      | 4         | Magic, `GSPR`                           |
+//! | 4      | 2         | Version, currently 1                    |
+//! | 6      | 2         | Reserved                                |
+//! | 8      | 4         | Number of slots                         |
+//! | 12     | 4         | Reserved                                |
+//! | 16     | 8         | Slot size                               |
+//! | 24     | 8         | Offset of the first slot, page aligned  |
+//! | 32     | 4 × slots | Slot states, 0 = free, 1 = filled       |
+//!
+//! Slot `i` starts at the offset of the first slot plus `i` times the slot size.
+//!
+//! For every frame, the element waits for a free slot, copies the frame to it, marks it filled
+//! and writes a slot record (see [`crate::length_prefixed`]) with the slot index, the frame size
+//! and the timestamps to stdin. The subprocess marks the slot free again once it is done with
+//! the frame, slots can be released in any order. Slot states are 32 bit atomics, stored with
+//! release and loaded with acquire ordering.
+//!
+//! ```no_run
+//! use gstsubprocesspipe::length_prefixed::{Reader, RecordKind};
+//! use gstsubprocesspipe::shm::Ring;
+//! use std::os::fd::{FromRawFd, OwnedFd};
+//!
+//! // Run as `cmd="consumer {shm_fd}"`
+//! let fd = std::env::args().nth(1).unwrap().parse().unwrap();
+//! let ring = Ring::from_fd(unsafe { OwnedFd::from_raw_fd(fd) }).unwrap();
+//!
+//! for record in Reader::new(std::io::stdin().lock()) {
+//!     let record = record.unwrap();
+//!     if record.header.kind == RecordKind::Slot {
+//!         let frame = ring.frame(&record).unwrap();
+//!         println!("frame: {} bytes at {:?}", frame.len(), record.header.pts);
+//!         // The slot is released when the frame is dropped
+//!     }
+//! }
+//! ```
+
+use std::fs::File;
+use std::io;
+use std::ops::Deref;
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd};
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::length_prefixed::Record;
+use crate::writer::{Waker, WriteError};
+
+pub const MAGIC: [u8; 4] = *b"GSPR";
+pub const VERSION: u16 = 1;
+pub const SLOT_FREE: u32 = 0;
+pub const SLOT_FILLED: u32 = 1;
+
+// Offset of the slot states
+const STATES_OFFSET: usize = 32;
+
+// The subprocess releases slots without notifying the element, which polls for a free slot
+static ACQUIRE_POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// A mapped ring buffer, created by the element or opened by the subprocess.
+#[derive(Debug)]
+pub struct Ring {
+    fd: OwnedFd,
+    ptr: NonNull<u8>,
+    len: usize,
+    slot_count: u32,
+    slot_size: usize,
+    data_offset: usize,
+    // Slot the element tries first for the next frame
+    next: u32,
+}
+
+// The mapping is owned by the ring, shared memory accesses go through the slot states
+unsafe impl Send for Ring {}
+
+impl Ring {
+    /// Creates a ring of `slot_count` slots of `slot_size` bytes in a new memfd.
+    pub(crate) fn create(slot_count: u32, slot_size: usize) -> io::Result<Self> {
+        let invalid = || io::Error::new(io::ErrorKind::InvalidInput, "Ring buffer too large");
+
+        let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+        let data_offset = (STATES_OFFSET + 4 * slot_count as usize).next_multiple_of(page_size);
+        let len = slot_size
+            .checked_mul(slot_count as usize)
+            .and_then(|size| size.checked_add(data_offset))
+            .ok_or_else(invalid)?;
+
+        let fd = unsafe {
+            libc::memfd_create(c"gst-subprocess-pipe-ring".as_ptr(), libc::MFD_CLOEXEC | libc::MFD_ALLOW_SEALING)
+        };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+        let size = libc::off_t::try_from(len).map_err(|_| invalid())?;
+        if unsafe { libc::ftruncate(fd.as_raw_fd(), size) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        // A subprocess shrinking the memfd would make accesses to the mapping fail with SIGBUS
+        let seals = libc::F_SEAL_SHRINK | libc::F_SEAL_GROW | libc::F_SEAL_SEAL;
+        if unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_ADD_SEALS, seals) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let ptr = map(fd.as_fd(), len)?;
+        let ring = Ring {
+            fd,
+            ptr,
+            len,
+            slot_count,
+            slot_size,
+            data_offset,
+            next: 0,
+        };
+
+        // The memfd is zero filled, so all slots start out free
+        let mut header = [0u8; STATES_OFFSET];
+        header[0..4].copy_from_slice(&MAGIC);
+        header[4..6].copy_from_slice(&VERSION.to_le_bytes());
+        header[8..12].copy_from_slice(&slot_count.to_le_bytes());
+        header[16..24].copy_from_slice(&(slot_size as u64).to_le_bytes());
+        header[24..32].copy_from_slice(&(data_offset as u64).to_le_bytes());
+        unsafe {
+            std::ptr::copy_nonoverlapping(header.as_ptr(), ring.ptr.as_ptr(), header.len());
+        }
+
+        Ok(ring)
+    }
+
+    /// Maps the ring passed to the subprocess as `fd`.
+    pub fn from_fd(fd: OwnedFd) -> io::Result<Self> {
+        let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+
+        let file = File::from(fd);
+        let len = usize::try_from(file.metadata()?.len()).map_err(|_| invalid("Ring buffer too large".into()))?;
+        if len < STATES_OFFSET {
+            return Err(invalid(format!("Ring buffer of {} bytes is too small", len)));
+        }
+
+        let fd = OwnedFd::from(file);
+        let ptr = map(fd.as_fd(), len)?;
+        let mut ring = Ring {
+            fd,
+            ptr,
+            len,
+            slot_count: 0,
+            slot_size: 0,
+            data_offset: 0,
+            next: 0,
+        };
+
+        let header = unsafe { std::slice::from_raw_parts(ring.ptr.as_ptr(), STATES_OFFSET) };
+        let u16_at = |pos: usize| u16::from_le_bytes(header[pos..pos + 2].try_into().unwrap());
+        let u32_at = |pos: usize| u32::from_le_bytes(header[pos..pos + 4].try_into().unwrap());
+        let u64_at = |pos: usize| u64::from_le_bytes(header[pos..pos + 8].try_into().unwrap());
+
+        if header[0..4] != MAGIC {
+            return Err(invalid(format!("Invalid magic {:?}", &header[0..4])));
+        }
+
+        let version = u16_at(4);
+        if version != VERSION {
+            return Err(invalid(format!("Unsupported version {}", version)));
+        }
+
+        let slot_count = u32_at(8);
+        let (slot_size, data_offset) = (u64_at(16), u64_at(24));
+        let end = slot_size
+            .checked_mul(slot_count as u64)
+            .and_then(|size| size.checked_add(data_offset));
+        if data_offset < (STATES_OFFSET + 4 * slot_count as usize) as u64 || end.is_none_or(|end| end > len as u64) {
+            return Err(invalid(format!(
+                "{} slots of {} bytes at offset {} don't fit in {} bytes",
+                slot_count, slot_size, data_offset, len
+            )));
+        }
+
+        ring.slot_count = slot_count;
+        ring.slot_size = slot_size as usize;
+        ring.data_offset = data_offset as usize;
+        Ok(ring)
+    }
+
+    pub fn slot_count(&self) -> u32 {
+        self.slot_count
+    }
+
+    pub fn slot_size(&self) -> usize {
+        self.slot_size
+    }
+
+    /// Copies `buffer` to a free slot, waiting for the subprocess to release one, and marks it
+    /// filled. Returns the index of the slot.
+    ///
+    /// With a `timeout`, gives up with [`WriteError::TimedOut`] if no slot was released in time.
+    pub(crate) fn write(
+        &mut self,
+        buffer: &gst::BufferRef,
+        waker: &Waker,
+        timeout: Option<Duration>,
+    ) -> Result<u32, WriteError> {
+        let size = buffer.size();
+        if size > self.slot_size {
+            return Err(WriteError::Io(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Buffer of {} bytes doesn't fit in slots of {} bytes", size, self.slot_size),
+            )));
+        }
+
+        let slot = self.acquire(waker, timeout)?;
+        let data = unsafe { std::slice::from_raw_parts_mut(self.slot_ptr(slot), size) };
+        buffer
+            .copy_to_slice(0, data)
+            .map_err(|_| io::Error::other("Failed to copy buffer"))?;
+
+        self.state(slot).store(SLOT_FILLED, Ordering::Release);
+        self.next = (slot + 1) % self.slot_count;
+        Ok(slot)
+    }
+
+    /// Marks a slot written by [`Ring::write`] free again, for frames whose slot record never
+    /// reached the subprocess.
+    pub(crate) fn release(&self, slot: u32) {
+        self.state(slot).store(SLOT_FREE, Ordering::Release);
+    }
+
+    // Wait for a free slot, starting the search at the slot after the last one written
+    fn acquire(&self, waker: &Waker, timeout: Option<Duration>) -> Result<u32, WriteError> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+
+        loop {
+            if waker.is_flushing() {
+                return Err(WriteError::Flushing);
+            }
+
+            let free = (0..self.slot_count)
+                .map(|i| (self.next + i) % self.slot_count)
+                .find(|slot| self.state(*slot).load(Ordering::Acquire) == SLOT_FREE);
+            if let Some(slot) = free {
+                return Ok(slot);
+            }
+
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                return Err(WriteError::TimedOut(0));
+            }
+
+            if !waker.sleep(ACQUIRE_POLL_INTERVAL) {
+                return Err(WriteError::Flushing);
+            }
+        }
+    }
+
+    /// The frame described by a slot record. The slot is released when the frame is dropped.
+    pub fn frame(&self, record: &Record) -> io::Result<Frame<'_>> {
+        let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+
+        let (slot, size) = record.slot().ok_or_else(|| invalid("Not a slot record".into()))?;
+        if slot >= self.slot_count || size > self.slot_size as u64 {
+            return Err(invalid(format!("Invalid slot {} with {} bytes", slot, size)));
+        }
+
+        if self.state(slot).load(Ordering::Acquire) != SLOT_FILLED {
+            return Err(invalid(format!("Slot {} is not filled", slot)));
+        }
+
+        Ok(Frame {
+            ring: self,
+            slot,
+            size: size as usize,
+        })
+    }
+
+    fn state(&self, slot: u32) -> &AtomicU32 {
+        debug_assert!(slot < self.slot_count);
+        unsafe { AtomicU32::from_ptr(self.ptr.as_ptr().add(STATES_OFFSET + 4 * slot as usize) as *mut u32) }
+    }
+
+    fn slot_ptr(&self, slot: u32) -> *mut u8 {
+        debug_assert!(slot < self.slot_count);
+        unsafe { self.ptr.as_ptr().add(self.data_offset + slot as usize * self.slot_size) }
+    }
+}
+
+impl AsFd for Ring {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+}
+
+impl Drop for Ring {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.ptr.as_ptr() as *mut libc::c_void, self.len);
+        }
+    }
+}
+
+/// A filled slot, released when dropped.
+#[derive(Debug)]
+pub struct Frame<'a> {
+    ring: &'a Ring,
+    slot: u32,
+    size: usize,
+}
+
+impl Frame<'_> {
+    pub fn slot(&self) -> u32 {
+        self.slot
+    }
+}
+
+impl Deref for Frame<'_> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.ring.slot_ptr(self.slot), self.size) }
+    }
+}
+
+impl Drop for Frame<'_> {
+    fn drop(&mut self) {
+        self.ring.state(self.slot).store(SLOT_FREE, Ordering::Release);
+    }
+}
+
+// Map the whole of `fd` shared and writable, the subprocess writes the slot states too
+fn map(fd: BorrowedFd, len: usize) -> io::Result<NonNull<u8>> {
+    let ptr = unsafe {
+        libc::mmap(
+            std::ptr::null_mut(),
+            len,
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_SHARED,
+            fd.as_raw_fd(),
+            0,
+        )
+    };
+    if ptr == libc::MAP_FAILED {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(NonNull::new(ptr as *mut u8).expect("mmap returned NULL"))
+}
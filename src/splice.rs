@@ -0,0 +1,211 @@
+// Copyright (C) 2025, Rafael Caricio <rafael@caricio.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License, v2.0.
+// If a copy of the MPL was not distributed with this file, You can obtain one at
+// <https://mozilla.org/MPL/2.0/>.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Writes of buffer memory to pipes with `vmsplice()`, without copying it.
+//!
+//! `vmsplice()` makes the pipe reference the pages of the memory instead of copying them, so the
+//! memory must not be freed or reused until the reader consumed it. The [`Splicer`] keeps the
+//! buffers alive until `FIONREAD` shows that the reader got past them, and until it is dropped
+//! once the subprocess exited.
+
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd};
+use std::time::{Duration, Instant};
+
+use crate::writer::{self, Waker, WriteError};
+
+// Memories below this size are copied, referencing them would take a whole pipe slot each
+const MIN_SPLICE_SIZE: usize = 4096;
+
+// Allocators whose memories map in place, a mapping of other memories may be a temporary copy
+// that is gone once unmapped
+const SPLICEABLE_MEMORY_TYPES: &[&str] = &["SystemMemory", "RustGlobalAllocatorMemory"];
+
+/// Resizes the pipe to at least `size` bytes, returning the actual size.
+///
+/// Unprivileged processes can't go above `/proc/sys/fs/pipe-max-size`.
+pub fn set_pipe_size(pipe: BorrowedFd, size: u32) -> io::Result<usize> {
+    let size = libc::c_int::try_from(size).unwrap_or(libc::c_int::MAX);
+    let res = unsafe { libc::fcntl(pipe.as_raw_fd(), libc::F_SETPIPE_SZ, size) };
+    if res < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(res as usize)
+}
+
+/// Writes buffers to a non-blocking pipe with `vmsplice()`, falling back to `write()` for
+/// memories that can't be spliced.
+#[derive(Debug, Default)]
+pub struct Splicer {
+    // Buffers the pipe may still reference, with the stream position of their end
+    buffers: VecDeque<(u64, gst::Buffer)>,
+    // Bytes passed to the pipe so far, spliced or written
+    position: u64,
+    // Set once vmsplice() failed in a way write() won't, e.g. on something else than a pipe
+    disabled: bool,
+}
+
+impl Splicer {
+    pub fn new() -> Self {
+        Splicer::default()
+    }
+
+    /// Writes `data` with a plain copy, keeping track of the stream position.
+    pub fn write_all<W: Write + AsFd>(
+        &mut self,
+        pipe: &mut W,
+        data: &[u8],
+        waker: &Waker,
+        timeout: Option<Duration>,
+    ) -> Result<(), WriteError> {
+        let mut output = Output {
+            pipe,
+            splice: false,
+            splicer: self,
+        };
+        writer::write_all(&mut output, data, waker, timeout)
+    }
+
+    /// Same as [`writer::write_buffer`], splicing the memories where possible. The buffer is
+    /// kept alive as long as the pipe may reference it.
+    pub fn write_buffer<W: Write + AsFd>(
+        &mut self,
+        pipe: &mut W,
+        buffer: &gst::Buffer,
+        offset: usize,
+        waker: &Waker,
+        timeout: Option<Duration>,
+    ) -> Result<(), WriteError> {
+        self.release(pipe.as_fd());
+
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let mut spliced = false;
+        let mut pos = 0;
+        let mut result = Ok(());
+
+        for memory in buffer.iter_memories() {
+            let size = memory.size();
+            if pos + size <= offset {
+                pos += size;
+                continue;
+            }
+
+            let map = match memory.map_readable() {
+                Ok(map) => map,
+                Err(_) => {
+                    result = Err(io::Error::other("Failed to map memory readable").into());
+                    break;
+                }
+            };
+            let skip = offset.saturating_sub(pos);
+
+            let splice = !self.disabled
+                && size - skip >= MIN_SPLICE_SIZE
+                && SPLICEABLE_MEMORY_TYPES.iter().any(|mem_type| memory.is_type(mem_type));
+            let position = self.position;
+            let mut output = Output {
+                pipe: &mut *pipe,
+                splice,
+                splicer: self,
+            };
+
+            let res = writer::write_all_until(&mut output, &map[skip..], waker, deadline);
+            spliced |= splice && self.position > position;
+            match res {
+                Ok(()) => (),
+                Err(WriteError::TimedOut(written)) => {
+                    result = Err(WriteError::TimedOut(pos + skip + written));
+                    break;
+                }
+                Err(err) => {
+                    result = Err(err);
+                    break;
+                }
+            }
+
+            pos += size;
+        }
+
+        // Also after errors, the pipe may hold the part that was spliced
+        if spliced {
+            self.buffers.push_back((self.position, buffer.clone()));
+        }
+
+        result
+    }
+
+    /// Number of buffers the pipe may still reference.
+    pub fn pending(&self) -> usize {
+        self.buffers.len()
+    }
+
+    // Drop the buffers that the reader got past. Everything but the unread bytes in the pipe
+    // was consumed.
+    fn release(&mut self, pipe: BorrowedFd) {
+        if self.buffers.is_empty() {
+            return;
+        }
+
+        let mut unread: libc::c_int = 0;
+        if unsafe { libc::ioctl(pipe.as_raw_fd(), libc::FIONREAD, &mut unread) } < 0 {
+            return;
+        }
+
+        let consumed = self.position.saturating_sub(unread as u64);
+        while self.buffers.front().is_some_and(|(end, _)| *end <= consumed) {
+            self.buffers.pop_front();
+        }
+    }
+}
+
+// Writes to the pipe with vmsplice() or write(), counting the bytes passed to it
+struct Output<'a, W> {
+    pipe: &'a mut W,
+    splice: bool,
+    splicer: &'a mut Splicer,
+}
+
+impl<W: Write + AsFd> Write for Output<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.splice && !self.splicer.disabled {
+            let iov = libc::iovec {
+                iov_base: buf.as_ptr() as *mut libc::c_void,
+                iov_len: buf.len(),
+            };
+            let res = unsafe { libc::vmsplice(self.pipe.as_fd().as_raw_fd(), &iov, 1, libc::SPLICE_F_NONBLOCK) };
+            if res >= 0 {
+                self.splicer.position += res as u64;
+                return Ok(res as usize);
+            }
+
+            let err = io::Error::last_os_error();
+            match err.raw_os_error() {
+                Some(libc::EINVAL | libc::EBADF | libc::ENOSYS | libc::EPERM) => {
+                    self.splicer.disabled = true;
+                }
+                _ => return Err(err),
+            }
+        }
+
+        let n = self.pipe.write(buf)?;
+        self.splicer.position += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.pipe.flush()
+    }
+}
+
+impl<W: AsFd> AsFd for Output<'_, W> {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.pipe.as_fd()
+    }
+}
@@ -0,0 +1,192 @@
+// Copyright (C) 2025, Rafael Caricio <rafael@caricio.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License, v2.0.
+// If a copy of the MPL was not distributed with this file, You can obtain one at
+// <https://mozilla.org/MPL/2.0/>.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Command line templating from negotiated caps.
+//!
+//! Placeholders such as `{width}` or `{ffmpeg_pix_fmt}` are replaced with values derived from
+//! the caps, `{fifo}` with the path of the named pipe of the FIFO transport, `{shm_fd}` with the
+//! file descriptor of the shared memory ring buffer and `{fd3}`, `{fd4}`, … with the
+//! `/dev/fd/N` paths of the extra pipes. Braces that don't name a known placeholder
+//! (e.g. shell `${VAR}` expansions) are left untouched.
+//!
+//! In a shell command, values that aren't made of safe characters only are single-quoted, so
+//! that e.g. a FIFO path with spaces stays one word. Placeholders must therefore not be quoted
+//! in the command itself.
+
+use gst::glib;
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use crate::audio;
+use crate::fds;
+use crate::pack;
+
+// All placeholders understood by the templating, whether or not the current caps provide them
+const PLACEHOLDERS: &[&str] = &[
+    "width",
+    "height",
+    "format",
+    "framerate",
+    "fps_n",
+    "fps_d",
+    "frame_size",
+    "ffmpeg_pix_fmt",
+    "rate",
+    "channels",
+    "ffmpeg_format",
+    "ffmpeg_sample_fmt",
+    "fifo",
+    "shm_fd",
+];
+
+fn is_placeholder(name: &str) -> bool {
+    PLACEHOLDERS.contains(&name) || fds::PLACEHOLDERS.contains(&name)
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Variables(HashMap<&'static str, String>);
+
+impl Variables {
+    pub fn from_caps(caps: &gst::CapsRef) -> Result<Self, glib::BoolError> {
+        let s = caps
+            .structure(0)
+            .ok_or_else(|| glib::bool_error!("Empty caps"))?;
+
+        let mut vars = HashMap::new();
+
+        if s.name() == "video/x-raw" {
+            let info = gst_video::VideoInfo::from_caps(caps)?;
+            let fps = info.fps();
+
+            vars.insert("width", info.width().to_string());
+            vars.insert("height", info.height().to_string());
+            vars.insert("format", info.format().to_str().to_string());
+            vars.insert("framerate", format!("{}/{}", fps.numer(), fps.denom()));
+            vars.insert("fps_n", fps.numer().to_string());
+            vars.insert("fps_d", fps.denom().to_string());
+            // Frames are written and read with tightly packed planes
+            vars.insert("frame_size", pack::packed_size(&info).to_string());
+            if let Some(pix_fmt) = ffmpeg_pix_fmt(info.format()) {
+                vars.insert("ffmpeg_pix_fmt", pix_fmt.to_string());
+            }
+        } else if s.name() == "audio/x-raw" {
+            let info = gst_audio::AudioInfo::from_caps(caps)?;
+
+            vars.insert("format", info.format().to_str().to_string());
+            vars.insert("rate", info.rate().to_string());
+            vars.insert("channels", info.channels().to_string());
+            if let Some(format) = audio::ffmpeg_format(&info.format_info()) {
+                vars.insert("ffmpeg_format", format);
+            }
+            if let Some(sample_fmt) = audio::ffmpeg_sample_fmt(&info.format_info()) {
+                vars.insert("ffmpeg_sample_fmt", sample_fmt.to_string());
+            }
+        }
+
+        Ok(Variables(vars))
+    }
+
+    /// Sets a placeholder that doesn't come from the caps, e.g. the path of a FIFO.
+    pub fn insert(&mut self, name: &'static str, value: String) {
+        debug_assert!(is_placeholder(name));
+        self.0.insert(name, value);
+    }
+
+    /// Replaces all known placeholders in `template`, shell-quoting the values for `sh -c` if
+    /// `shell` is set.
+    ///
+    /// Fails if the template uses a placeholder that the current caps and settings can't provide.
+    pub fn expand(&self, template: &str, shell: bool) -> Result<String, String> {
+        let mut out = String::with_capacity(template.len());
+        let mut rest = template;
+
+        while let Some(start) = rest.find('{') {
+            out.push_str(&rest[..start]);
+            rest = &rest[start..];
+
+            let name = rest[1..]
+                .find('}')
+                .map(|end| &rest[1..end + 1])
+                .filter(|name| is_placeholder(name));
+
+            match name {
+                Some(name) => {
+                    let value = self.0.get(name).ok_or_else(|| {
+                        format!("Placeholder {{{}}} is not available for the negotiated caps and settings", name)
+                    })?;
+                    if shell {
+                        out.push_str(&shell_quote(value));
+                    } else {
+                        out.push_str(value);
+                    }
+                    rest = &rest[name.len() + 2..];
+                }
+                None => {
+                    out.push('{');
+                    rest = &rest[1..];
+                }
+            }
+        }
+
+        out.push_str(rest);
+        Ok(out)
+    }
+}
+
+// Quotes `value` as a single shell word, unless it only has characters without special meaning
+fn shell_quote(value: &str) -> Cow<'_, str> {
+    let safe = |c: char| c.is_ascii_alphanumeric() || "_-./:=+,@%".contains(c);
+    if !value.is_empty() && value.chars().all(safe) {
+        return Cow::Borrowed(value);
+    }
+
+    Cow::Owned(format!("'{}'", value.replace('\'', "'\\''")))
+}
+
+// Maps GStreamer raw video formats to the equivalent ffmpeg `pix_fmt` names
+pub fn ffmpeg_pix_fmt(format: gst_video::VideoFormat) -> Option<&'static str> {
+    use gst_video::VideoFormat;
+
+    let pix_fmt = match format {
+        VideoFormat::I420 => "yuv420p",
+        VideoFormat::Y42b => "yuv422p",
+        VideoFormat::Y444 => "yuv444p",
+        VideoFormat::Y41b => "yuv411p",
+        VideoFormat::A420 => "yuva420p",
+        VideoFormat::Nv12 => "nv12",
+        VideoFormat::Nv21 => "nv21",
+        VideoFormat::Nv16 => "nv16",
+        VideoFormat::Nv24 => "nv24",
+        VideoFormat::Yuy2 => "yuyv422",
+        VideoFormat::Uyvy => "uyvy422",
+        VideoFormat::Yvyu => "yvyu422",
+        VideoFormat::I42010le => "yuv420p10le",
+        VideoFormat::I42210le => "yuv422p10le",
+        VideoFormat::Y44410le => "yuv444p10le",
+        VideoFormat::I42012le => "yuv420p12le",
+        VideoFormat::P01010le => "p010le",
+        VideoFormat::Rgb => "rgb24",
+        VideoFormat::Bgr => "bgr24",
+        VideoFormat::Rgba => "rgba",
+        VideoFormat::Bgra => "bgra",
+        VideoFormat::Argb => "argb",
+        VideoFormat::Abgr => "abgr",
+        VideoFormat::Rgbx => "rgb0",
+        VideoFormat::Bgrx => "bgr0",
+        VideoFormat::Xrgb => "0rgb",
+        VideoFormat::Xbgr => "0bgr",
+        VideoFormat::Gbr => "gbrp",
+        VideoFormat::Gbra => "gbrap",
+        VideoFormat::Gray8 => "gray",
+        VideoFormat::Gray16Le => "gray16le",
+        VideoFormat::Gray16Be => "gray16be",
+        _ => return None,
+    };
+
+    Some(pix_fmt)
+}
@@ -0,0 +1,226 @@
+// Copyright (C) 2025, Rafael Caricio <rafael@caricio.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License, v2.0.
+// If a copy of the MPL was not distributed with this file, You can obtain one at
+// <https://mozilla.org/MPL/2.0/>.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Channels other than stdin that carry the frames to the subprocess, or to a process that is
+//! already running.
+
+use gst::glib;
+use std::ffi::CString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd};
+use std::os::linux::net::SocketAddrExt;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{DirBuilderExt, FileTypeExt, OpenOptionsExt};
+use std::os::unix::net::{SocketAddr, UnixStream};
+use std::path::{Path, PathBuf};
+use std::process::Child;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::writer::{Waker, WriteError};
+
+#[derive(Debug, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Clone, Copy, glib::Enum)]
+#[repr(u32)]
+#[enum_type(name = "GstSubprocessPipeTransport")]
+pub enum Transport {
+    #[default]
+    #[enum_value(name = "Stdin: Write to the standard input of the subprocess", nick = "stdin")]
+    Stdin,
+    #[enum_value(name = "FIFO: Write to a named pipe passed to the subprocess as {fifo}", nick = "fifo")]
+    Fifo,
+    #[enum_value(
+        name = "Unix socket: Connect to the Unix domain socket of a running process instead of spawning one",
+        nick = "unix-socket"
+    )]
+    UnixSocket,
+    #[enum_value(
+        name = "Shared memory: Copy frames to a ring buffer passed to the subprocess as {shm_fd}, with slot records on stdin",
+        nick = "shm"
+    )]
+    Shm,
+}
+
+/// Where the frames are written to.
+#[derive(Debug)]
+pub enum Input {
+    // Stdin of the subprocess or a FIFO
+    Pipe(File),
+    Socket(UnixStream),
+}
+
+impl Write for Input {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Input::Pipe(file) => file.write(buf),
+            Input::Socket(stream) => {
+                // Without MSG_NOSIGNAL a peer that went away would kill the process with SIGPIPE
+                // instead of failing with EPIPE
+                let res = unsafe {
+                    libc::send(
+                        stream.as_raw_fd(),
+                        buf.as_ptr() as *const libc::c_void,
+                        buf.len(),
+                        libc::MSG_NOSIGNAL,
+                    )
+                };
+                if res < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(res as usize)
+            }
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl AsFd for Input {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        match self {
+            Input::Pipe(file) => file.as_fd(),
+            Input::Socket(stream) => stream.as_fd(),
+        }
+    }
+}
+
+/// Connects to the stream socket at `path`, in the abstract namespace if it starts with `@`.
+///
+/// The connection is non-blocking so that unlock() can interrupt writes.
+pub fn connect(path: &str) -> io::Result<UnixStream> {
+    let stream = match path.strip_prefix('@') {
+        Some(name) => UnixStream::connect_addr(&SocketAddr::from_abstract_name(name.as_bytes())?)?,
+        None => UnixStream::connect(path)?,
+    };
+    stream.set_nonblocking(true)?;
+    Ok(stream)
+}
+
+/// Whether a write error means that the peer closed the connection.
+pub fn is_disconnect(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::BrokenPipe | io::ErrorKind::ConnectionReset | io::ErrorKind::NotConnected
+    )
+}
+
+// Interval between attempts to open a FIFO that has no reader yet
+static FIFO_OPEN_INTERVAL: Duration = Duration::from_millis(10);
+
+/// A named pipe, removed again when dropped if it was created by us.
+#[derive(Debug)]
+pub struct Fifo {
+    path: PathBuf,
+    created: bool,
+    // Temporary directory created for the FIFO, removed together with it
+    dir: Option<PathBuf>,
+}
+
+impl Fifo {
+    /// Creates a FIFO at `path`, or in a new private temporary directory if not set.
+    ///
+    /// An existing FIFO at `path` is used as it is.
+    pub fn create(path: Option<&Path>) -> io::Result<Self> {
+        let (path, dir) = match path {
+            Some(path) => (path.to_path_buf(), None),
+            None => {
+                static COUNTER: AtomicU32 = AtomicU32::new(0);
+                let dir = std::env::temp_dir().join(format!(
+                    "gst-subprocess-pipe-{}-{}",
+                    std::process::id(),
+                    COUNTER.fetch_add(1, Ordering::Relaxed)
+                ));
+                fs::DirBuilder::new().mode(0o700).create(&dir)?;
+                (dir.join("frames"), Some(dir))
+            }
+        };
+
+        let mut fifo = Fifo {
+            path,
+            created: false,
+            dir,
+        };
+
+        match fs::metadata(&fifo.path) {
+            Ok(metadata) if metadata.file_type().is_fifo() => return Ok(fifo),
+            Ok(_) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!("{} exists and is not a FIFO", fifo.path.display()),
+                ));
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => (),
+            Err(err) => return Err(err),
+        }
+
+        let c_path = CString::new(fifo.path.as_os_str().as_bytes())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "FIFO path contains a NUL byte"))?;
+        if unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        fifo.created = true;
+
+        Ok(fifo)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Opens the FIFO for writing once `child` opened it for reading.
+    ///
+    /// A blocking open would wait forever for a subprocess that never opens the FIFO, so the
+    /// non-blocking open is retried until it succeeds, the subprocess exits, the waker fires or
+    /// `timeout` passed, which fails with [`WriteError::TimedOut`].
+    pub fn open_writer(&self, child: &mut Child, waker: &Waker, timeout: Duration) -> Result<File, WriteError> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let res = OpenOptions::new()
+                .write(true)
+                .custom_flags(libc::O_NONBLOCK)
+                .open(&self.path);
+
+            match res {
+                Ok(file) => return Ok(file),
+                // No reader yet
+                Err(err) if err.raw_os_error() == Some(libc::ENXIO) => (),
+                Err(err) => return Err(err.into()),
+            }
+
+            if let Some(status) = child.try_wait()? {
+                return Err(WriteError::Io(io::Error::new(
+                    io::ErrorKind::BrokenPipe,
+                    format!("Subprocess exited with {} without opening the FIFO", status),
+                )));
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(WriteError::TimedOut(0));
+            }
+
+            if !waker.sleep(FIFO_OPEN_INTERVAL.min(deadline - now)) {
+                return Err(WriteError::Flushing);
+            }
+        }
+    }
+}
+
+impl Drop for Fifo {
+    fn drop(&mut self) {
+        if self.created {
+            let _ = fs::remove_file(&self.path);
+        }
+
+        if let Some(dir) = &self.dir {
+            let _ = fs::remove_dir(dir);
+        }
+    }
+}
@@ -0,0 +1,365 @@
+// Copyright (C) 2025, Rafael Caricio <rafael@caricio.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License, v2.0.
+// If a copy of the MPL was not distributed with this file, You can obtain one at
+// <https://mozilla.org/MPL/2.0/>.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Interruptible writes to, and reads from, non-blocking pipes.
+//!
+//! A write or read that would block waits in `poll()` on both the pipe and a [`Waker`], so that
+//! `unlock()` can interrupt a render stuck on a subprocess that stopped reading, or a source
+//! waiting for a subprocess to produce output.
+
+use std::io::{self, Read, Write};
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+#[derive(Debug)]
+pub enum WriteError {
+    // Interrupted by the waker
+    Flushing,
+    // Timed out after writing the given number of bytes
+    TimedOut(usize),
+    Io(io::Error),
+}
+
+impl From<io::Error> for WriteError {
+    fn from(err: io::Error) -> Self {
+        WriteError::Io(err)
+    }
+}
+
+/// Wakes up writers blocked in [`write_all`] and keeps them from blocking again until reset.
+#[derive(Debug)]
+pub struct Waker {
+    read: OwnedFd,
+    write: OwnedFd,
+    flushing: AtomicBool,
+}
+
+impl Waker {
+    pub fn new() -> io::Result<Self> {
+        let mut fds = [0; 2];
+        if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC | libc::O_NONBLOCK) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(Waker {
+            read: unsafe { OwnedFd::from_raw_fd(fds[0]) },
+            write: unsafe { OwnedFd::from_raw_fd(fds[1]) },
+            flushing: AtomicBool::new(false),
+        })
+    }
+
+    pub fn wake(&self) {
+        self.flushing.store(true, Ordering::SeqCst);
+        unsafe {
+            libc::write(self.write.as_raw_fd(), [1u8].as_ptr() as *const libc::c_void, 1);
+        }
+    }
+
+    pub fn reset(&self) {
+        self.flushing.store(false, Ordering::SeqCst);
+
+        let mut buf = [0u8; 64];
+        while unsafe {
+            libc::read(self.read.as_raw_fd(), buf.as_mut_ptr() as *mut libc::c_void, buf.len())
+        } > 0
+        {}
+    }
+
+    pub fn is_flushing(&self) -> bool {
+        self.flushing.load(Ordering::SeqCst)
+    }
+
+    /// Sleeps for `timeout` unless woken before. Returns false if woken.
+    pub fn sleep(&self, timeout: Duration) -> bool {
+        let mut fds = [libc::pollfd {
+            fd: self.read.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        }];
+        let timeout_ms = timeout.as_millis().min(libc::c_int::MAX as u128) as libc::c_int;
+
+        unsafe {
+            libc::poll(fds.as_mut_ptr(), 1, timeout_ms);
+        }
+
+        fds[0].revents == 0 && !self.is_flushing()
+    }
+}
+
+/// Puts a file descriptor in non-blocking mode.
+pub fn set_nonblocking(fd: BorrowedFd) -> io::Result<()> {
+    let raw = fd.as_raw_fd();
+    let flags = unsafe { libc::fcntl(raw, libc::F_GETFL) };
+    if flags < 0 || unsafe { libc::fcntl(raw, libc::F_SETFL, flags | libc::O_NONBLOCK) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+/// Writes all of `data` to a non-blocking writer, waiting for it to become writable as needed.
+///
+/// With a `timeout`, gives up once the whole of `data` couldn't be written in time.
+pub fn write_all<W: Write + AsFd>(
+    writer: &mut W,
+    data: &[u8],
+    waker: &Waker,
+    timeout: Option<Duration>,
+) -> Result<(), WriteError> {
+    write_all_until(writer, data, waker, timeout.map(|timeout| Instant::now() + timeout))
+}
+
+/// Writes the memories of `buffer` one by one, starting at `offset` bytes into the buffer.
+///
+/// This avoids merging multi-memory buffers, e.g. a framing header followed by the frame. On
+/// timeout, the error holds the number of bytes of the buffer written so far.
+pub fn write_buffer<W: Write + AsFd>(
+    writer: &mut W,
+    buffer: &gst::BufferRef,
+    offset: usize,
+    waker: &Waker,
+    timeout: Option<Duration>,
+) -> Result<(), WriteError> {
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    let mut pos = 0;
+
+    for memory in buffer.iter_memories() {
+        let size = memory.size();
+        if pos + size <= offset {
+            pos += size;
+            continue;
+        }
+
+        let map = memory
+            .map_readable()
+            .map_err(|_| io::Error::other("Failed to map memory readable"))?;
+        let skip = offset.saturating_sub(pos);
+
+        match write_all_until(writer, &map[skip..], waker, deadline) {
+            Err(WriteError::TimedOut(written)) => return Err(WriteError::TimedOut(pos + skip + written)),
+            result => result?,
+        }
+
+        pos += size;
+    }
+
+    Ok(())
+}
+
+/// Same as [`write_all`], with a deadline instead of a timeout.
+pub fn write_all_until<W: Write + AsFd>(
+    writer: &mut W,
+    data: &[u8],
+    waker: &Waker,
+    deadline: Option<Instant>,
+) -> Result<(), WriteError> {
+    let mut written = 0;
+
+    while written < data.len() {
+        if waker.is_flushing() {
+            return Err(WriteError::Flushing);
+        }
+
+        let result = {
+            let sigpipe = SigpipeBlock::new();
+            let result = writer.write(&data[written..]);
+            if result.as_ref().is_err_and(|err| err.kind() == io::ErrorKind::BrokenPipe) {
+                sigpipe.discard();
+            }
+            result
+        };
+
+        match result {
+            Ok(0) => return Err(io::Error::from(io::ErrorKind::WriteZero).into()),
+            Ok(n) => written += n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => (),
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                let remaining = deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+                if remaining.is_some_and(|remaining| remaining.is_zero()) {
+                    return Err(WriteError::TimedOut(written));
+                }
+
+                poll(writer.as_fd(), libc::POLLOUT, waker, remaining)?;
+            }
+            Err(err) => return Err(err.into()),
+        }
+    }
+
+    Ok(())
+}
+
+/// Waits for `fd` to become writable, failing with [`WriteError::TimedOut`] after `timeout`.
+///
+/// The next write then makes progress, so a stall can be detected before any data was written.
+pub fn wait_writable(fd: BorrowedFd, waker: &Waker, timeout: Option<Duration>) -> Result<(), WriteError> {
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+
+    loop {
+        let mut pollfd = libc::pollfd {
+            fd: fd.as_raw_fd(),
+            events: libc::POLLOUT,
+            revents: 0,
+        };
+        // Errors and hangups are reported by the next write
+        if unsafe { libc::poll(&mut pollfd, 1, 0) } > 0 {
+            return Ok(());
+        }
+
+        let remaining = deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+        if remaining.is_some_and(|remaining| remaining.is_zero()) {
+            return Err(WriteError::TimedOut(0));
+        }
+
+        poll(fd, libc::POLLOUT, waker, remaining)?;
+    }
+}
+
+/// Blocks SIGPIPE in the calling thread while alive.
+///
+/// Writing to a pipe whose reader exited raises SIGPIPE, which kills the process unless the
+/// application ignores it, as e.g. `gst-launch-1.0` doesn't. With the signal blocked the write
+/// fails with EPIPE instead, and the subprocess can be restarted.
+struct SigpipeBlock {
+    old_mask: libc::sigset_t,
+    // Already pending before, so not raised by our write
+    was_pending: bool,
+}
+
+impl SigpipeBlock {
+    fn new() -> Self {
+        unsafe {
+            let mut pending = std::mem::zeroed();
+            libc::sigemptyset(&mut pending);
+            libc::sigpending(&mut pending);
+            let was_pending = libc::sigismember(&pending, libc::SIGPIPE) == 1;
+
+            let mut old_mask = std::mem::zeroed();
+            libc::pthread_sigmask(libc::SIG_BLOCK, &sigpipe_set(), &mut old_mask);
+
+            SigpipeBlock { old_mask, was_pending }
+        }
+    }
+
+    /// Takes the SIGPIPE raised by a failed write, so that it isn't delivered once unblocked.
+    fn discard(&self) {
+        if self.was_pending {
+            return;
+        }
+
+        let timeout = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+        unsafe {
+            while libc::sigtimedwait(&sigpipe_set(), std::ptr::null_mut(), &timeout) < 0
+                && io::Error::last_os_error().kind() == io::ErrorKind::Interrupted
+            {}
+        }
+    }
+}
+
+impl Drop for SigpipeBlock {
+    fn drop(&mut self) {
+        unsafe {
+            libc::pthread_sigmask(libc::SIG_SETMASK, &self.old_mask, std::ptr::null_mut());
+        }
+    }
+}
+
+fn sigpipe_set() -> libc::sigset_t {
+    unsafe {
+        let mut set = std::mem::zeroed();
+        libc::sigemptyset(&mut set);
+        libc::sigaddset(&mut set, libc::SIGPIPE);
+        set
+    }
+}
+
+/// Reads from a non-blocking reader, blocking until data is available or the waker fires.
+///
+/// Reads interrupted by the waker fail, check [`Waker::is_flushing`] to tell them apart from
+/// other errors.
+#[derive(Debug)]
+pub struct InterruptibleReader<R> {
+    inner: R,
+    waker: Arc<Waker>,
+}
+
+impl<R: Read + AsFd> InterruptibleReader<R> {
+    pub fn new(inner: R, waker: Arc<Waker>) -> io::Result<Self> {
+        set_nonblocking(inner.as_fd())?;
+        Ok(InterruptibleReader { inner, waker })
+    }
+}
+
+impl<R: Read + AsFd> Read for InterruptibleReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if self.waker.is_flushing() {
+                return Err(io::Error::other("Read interrupted"));
+            }
+
+            match self.inner.read(buf) {
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                    match poll(self.inner.as_fd(), libc::POLLIN, &self.waker, None) {
+                        Ok(()) => (),
+                        Err(WriteError::Io(err)) => return Err(err),
+                        Err(_) => return Err(io::Error::other("Read interrupted")),
+                    }
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// Reads up to `size` bytes, less only at the end of the stream.
+pub fn read_up_to(reader: &mut impl Read, size: usize) -> io::Result<Vec<u8>> {
+    let mut data = Vec::with_capacity(size);
+    reader.take(size as u64).read_to_end(&mut data)?;
+    Ok(data)
+}
+
+// Block until the fd is ready for `events`, the waker fires or the timeout expires
+fn poll(fd: BorrowedFd, events: libc::c_short, waker: &Waker, timeout: Option<Duration>) -> Result<(), WriteError> {
+    let mut fds = [
+        libc::pollfd {
+            fd: fd.as_raw_fd(),
+            events,
+            revents: 0,
+        },
+        libc::pollfd {
+            fd: waker.read.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        },
+    ];
+
+    // Round up so that we don't spin on sub-millisecond timeouts
+    let timeout_ms = timeout.map_or(-1, |timeout| {
+        timeout.as_nanos().div_ceil(1_000_000).min(libc::c_int::MAX as u128) as libc::c_int
+    });
+
+    loop {
+        let res = unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, timeout_ms) };
+        if res >= 0 {
+            break;
+        }
+
+        let err = io::Error::last_os_error();
+        if err.kind() != io::ErrorKind::Interrupted {
+            return Err(err.into());
+        }
+    }
+
+    if fds[1].revents != 0 || waker.is_flushing() {
+        return Err(WriteError::Flushing);
+    }
+
+    // Timeouts and POLLERR/POLLHUP are reported by the next write or read
+    Ok(())
+}
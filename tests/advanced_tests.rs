@@ -2,6 +2,7 @@ use std::fs::{self, File};
 use std::io::Read;
 use std::path::Path;
 use std::process;
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 use std::thread;
 
@@ -562,3 +563,2240 @@ fn test_subprocess_output_verification() {
     // Clean up the temporary file
     fs::remove_file(temp_file).ok();
 }
+
+#[test]
+#[serial]
+fn test_argv_mode_without_shell() {
+    init();
+
+    let pipeline = gst::Pipeline::new();
+
+    let src = gst::ElementFactory::make("videotestsrc")
+        .build()
+        .expect("Failed to create videotestsrc");
+    src.set_property("num-buffers", 1i32);
+
+    let capsfilter = gst::ElementFactory::make("capsfilter")
+        .build()
+        .expect("Failed to create capsfilter");
+    let caps = gst::Caps::builder("video/x-raw")
+        .field("format", "RGB")
+        .field("width", 64i32)
+        .field("height", 64i32)
+        .build();
+    capsfilter.set_property("caps", caps);
+
+    let sink = gst::ElementFactory::make("videopipesink")
+        .build()
+        .expect("Failed to create videopipesink");
+
+    // A path with spaces and quotes would be mangled by `sh -c`
+    let temp_file = create_temp_filepath("with spaces 'and quotes'.rgb");
+    sink.set_property("program", "tee");
+    sink.set_property("argv", gst::Array::new([temp_file.as_str()]));
+
+    pipeline.add_many(&[&src, &capsfilter, &sink]).unwrap();
+    gst::Element::link_many(&[&src, &capsfilter, &sink]).expect("Failed to link elements");
+
+    pipeline.set_state(gst::State::Playing).expect("Failed to set pipeline to Playing");
+
+    let msg = wait_for_message(
+        &pipeline,
+        gst::ClockTime::from_seconds(5),
+        &[gst::MessageType::Eos, gst::MessageType::Error],
+    );
+
+    pipeline.set_state(gst::State::Null).expect("Failed to set pipeline to Null");
+
+    match msg.as_ref().map(|msg| msg.view()) {
+        Some(gst::MessageView::Eos(..)) => {}
+        Some(gst::MessageView::Error(err)) => panic!("Error from pipeline: {}", err.error()),
+        _ => panic!("No EOS or Error message received within timeout"),
+    }
+
+    let metadata = fs::metadata(&temp_file).expect("Output file not created");
+    assert_eq!(metadata.len(), 12_288);
+
+    fs::remove_file(temp_file).ok();
+}
+
+#[test]
+#[serial]
+fn test_cmd_and_argv_are_mutually_exclusive() {
+    init();
+
+    let pipeline = build_pipeline("cat > /dev/null", 1);
+    let sink = pipeline.iterate_sinks().next().unwrap().unwrap();
+    sink.set_property("argv", gst::Array::new(["cat"]));
+
+    let result = pipeline.set_state(gst::State::Playing);
+    let msg = wait_for_message(
+        &pipeline,
+        gst::ClockTime::from_seconds(2),
+        &[gst::MessageType::Error],
+    );
+
+    pipeline.set_state(gst::State::Null).unwrap();
+
+    assert!(result.is_err() || msg.is_some(), "Expected start() to reject cmd together with argv");
+}
+
+#[test]
+#[serial]
+fn test_command_templating_from_caps() {
+    init();
+
+    let pipeline = gst::Pipeline::new();
+
+    let src = gst::ElementFactory::make("videotestsrc")
+        .build()
+        .expect("Failed to create videotestsrc");
+    src.set_property("num-buffers", 1i32);
+
+    let capsfilter = gst::ElementFactory::make("capsfilter")
+        .build()
+        .expect("Failed to create capsfilter");
+    let caps = gst::Caps::builder("video/x-raw")
+        .field("format", "I420")
+        .field("width", 320i32)
+        .field("height", 240i32)
+        .field("framerate", gst::Fraction::new(25, 1))
+        .build();
+    capsfilter.set_property("caps", caps);
+
+    let sink = gst::ElementFactory::make("videopipesink")
+        .build()
+        .expect("Failed to create videopipesink");
+
+    let temp_file = create_temp_filepath("txt");
+    let cmd = format!(
+        "echo {{width}}x{{height}} {{format}} {{framerate}} {{ffmpeg_pix_fmt}} > {}; cat > /dev/null",
+        temp_file
+    );
+    sink.set_property("cmd", cmd);
+
+    pipeline.add_many(&[&src, &capsfilter, &sink]).unwrap();
+    gst::Element::link_many(&[&src, &capsfilter, &sink]).expect("Failed to link elements");
+
+    pipeline.set_state(gst::State::Playing).expect("Failed to set pipeline to Playing");
+
+    let msg = wait_for_message(
+        &pipeline,
+        gst::ClockTime::from_seconds(5),
+        &[gst::MessageType::Eos, gst::MessageType::Error],
+    );
+
+    pipeline.set_state(gst::State::Null).expect("Failed to set pipeline to Null");
+
+    match msg.as_ref().map(|msg| msg.view()) {
+        Some(gst::MessageView::Eos(..)) => {}
+        Some(gst::MessageView::Error(err)) => panic!("Error from pipeline: {}", err.error()),
+        _ => panic!("No EOS or Error message received within timeout"),
+    }
+
+    let output = fs::read_to_string(&temp_file).expect("Output file not created");
+    assert_eq!(output.trim(), "320x240 I420 25/1 yuv420p");
+
+    fs::remove_file(temp_file).ok();
+}
+
+// Pushes one frame per caps through an appsrc, so the sink sees a renegotiation
+fn run_caps_change_pipeline(sink: &gst::Element, sizes: &[(i32, i32)]) -> Option<gst::Message> {
+    let pipeline = gst::Pipeline::new();
+
+    let src = gst::ElementFactory::make("appsrc")
+        .property_from_str("format", "time")
+        .build()
+        .expect("Failed to create appsrc");
+
+    pipeline.add_many(&[&src, sink]).unwrap();
+    src.link(sink).expect("Failed to link elements");
+
+    pipeline.set_state(gst::State::Playing).expect("Failed to set pipeline to Playing");
+
+    for (i, &(width, height)) in sizes.iter().enumerate() {
+        let caps = gst::Caps::builder("video/x-raw")
+            .field("format", "GRAY8")
+            .field("width", width)
+            .field("height", height)
+            .field("framerate", gst::Fraction::new(30, 1))
+            .build();
+        src.set_property("caps", caps);
+
+        let mut buffer = gst::Buffer::from_mut_slice(vec![0u8; (width * height) as usize]);
+        buffer.get_mut().unwrap().set_pts(gst::ClockTime::from_mseconds(33 * i as u64));
+        let _: gst::FlowReturn = src.emit_by_name("push-buffer", &[&buffer]);
+    }
+    let _: gst::FlowReturn = src.emit_by_name("end-of-stream", &[]);
+
+    let msg = wait_for_message(
+        &pipeline,
+        gst::ClockTime::from_seconds(5),
+        &[gst::MessageType::Eos, gst::MessageType::Error],
+    );
+
+    pipeline.set_state(gst::State::Null).expect("Failed to set pipeline to Null");
+
+    msg
+}
+
+#[test]
+#[serial]
+fn test_caps_change_restarts_subprocess() {
+    init();
+
+    let sink = gst::ElementFactory::make("videopipesink")
+        .build()
+        .expect("Failed to create videopipesink");
+
+    let temp_file = create_temp_filepath("txt");
+    sink.set_property("cmd", format!("echo {{width}}x{{height}} >> {}; cat > /dev/null", temp_file));
+    sink.set_property_from_str("on-caps-change", "restart");
+
+    let msg = run_caps_change_pipeline(&sink, &[(64, 48), (32, 24)]);
+
+    match msg.as_ref().map(|msg| msg.view()) {
+        Some(gst::MessageView::Eos(..)) => {}
+        Some(gst::MessageView::Error(err)) => panic!("Error from pipeline: {}", err.error()),
+        _ => panic!("No EOS or Error message received within timeout"),
+    }
+
+    // One line per spawned subprocess
+    let output = fs::read_to_string(&temp_file).expect("Output file not created");
+    assert_eq!(output.lines().collect::<Vec<_>>(), ["64x48", "32x24"]);
+
+    fs::remove_file(temp_file).ok();
+}
+
+#[test]
+#[serial]
+fn test_caps_change_error() {
+    init();
+
+    let sink = gst::ElementFactory::make("videopipesink")
+        .build()
+        .expect("Failed to create videopipesink");
+    sink.set_property("cmd", "cat > /dev/null");
+    sink.set_property_from_str("on-caps-change", "error");
+
+    let msg = run_caps_change_pipeline(&sink, &[(64, 48), (32, 24)]);
+
+    assert!(
+        matches!(msg.as_ref().map(|msg| msg.view()), Some(gst::MessageView::Error(..))),
+        "Expected an error on caps change"
+    );
+}
+
+#[test]
+#[serial]
+fn test_subprocess_environment() {
+    init();
+
+    std::env::set_var("GST_SUBPROCESS_PIPE_TEST_INHERITED", "inherited");
+
+    for clear_env in [false, true] {
+        let temp_file = create_temp_filepath("env");
+        let cmd = format!(
+            "echo \"$MODEL_PATH:$GST_SUBPROCESS_PIPE_TEST_INHERITED\" > {}; cat > /dev/null",
+            temp_file
+        );
+
+        let pipeline = build_pipeline(&cmd, 1);
+        let sink = pipeline.iterate_sinks().next().unwrap().unwrap();
+        sink.set_property("env", gst::Array::new(["MODEL_PATH=/models/a b"]));
+        sink.set_property("clear-env", clear_env);
+
+        pipeline.set_state(gst::State::Playing).expect("Failed to set pipeline to Playing");
+
+        let msg = wait_for_message(
+            &pipeline,
+            gst::ClockTime::from_seconds(5),
+            &[gst::MessageType::Eos, gst::MessageType::Error],
+        );
+
+        pipeline.set_state(gst::State::Null).expect("Failed to set pipeline to Null");
+
+        match msg.as_ref().map(|msg| msg.view()) {
+            Some(gst::MessageView::Eos(..)) => {}
+            Some(gst::MessageView::Error(err)) => panic!("Error from pipeline: {}", err.error()),
+            _ => panic!("No EOS or Error message received within timeout"),
+        }
+
+        let output = fs::read_to_string(&temp_file).expect("Output file not created");
+        let expected = if clear_env { "/models/a b:" } else { "/models/a b:inherited" };
+        assert_eq!(output.trim_end(), expected);
+
+        fs::remove_file(temp_file).ok();
+    }
+}
+
+#[test]
+#[serial]
+fn test_working_directory() {
+    init();
+
+    let work_dir = create_temp_filepath("workdir");
+    fs::create_dir_all(&work_dir).unwrap();
+
+    let pipeline = build_pipeline("cat > relative_output.raw", 1);
+    let sink = pipeline.iterate_sinks().next().unwrap().unwrap();
+    sink.set_property("working-directory", &work_dir);
+
+    pipeline.set_state(gst::State::Playing).expect("Failed to set pipeline to Playing");
+
+    let msg = wait_for_message(
+        &pipeline,
+        gst::ClockTime::from_seconds(5),
+        &[gst::MessageType::Eos, gst::MessageType::Error],
+    );
+
+    pipeline.set_state(gst::State::Null).expect("Failed to set pipeline to Null");
+
+    match msg.as_ref().map(|msg| msg.view()) {
+        Some(gst::MessageView::Eos(..)) => {}
+        Some(gst::MessageView::Error(err)) => panic!("Error from pipeline: {}", err.error()),
+        _ => panic!("No EOS or Error message received within timeout"),
+    }
+
+    assert!(Path::new(&work_dir).join("relative_output.raw").exists(), "Output file not created");
+
+    fs::remove_dir_all(work_dir).ok();
+}
+
+#[test]
+#[serial]
+fn test_missing_working_directory() {
+    init();
+
+    let pipeline = build_pipeline("cat > /dev/null", 1);
+    let sink = pipeline.iterate_sinks().next().unwrap().unwrap();
+    sink.set_property("working-directory", "/nonexistent/gst-subprocess-pipe");
+
+    let result = pipeline.set_state(gst::State::Playing);
+    let msg = wait_for_message(
+        &pipeline,
+        gst::ClockTime::from_seconds(2),
+        &[gst::MessageType::Error],
+    );
+
+    pipeline.set_state(gst::State::Null).unwrap();
+
+    assert!(result.is_err(), "Expected start() to fail");
+    let msg = msg.expect("Expected an error message");
+    match msg.view() {
+        gst::MessageView::Error(err) => {
+            assert!(err.error().matches(gst::ResourceError::NotFound));
+        }
+        _ => unreachable!(),
+    }
+}
+
+#[test]
+#[serial]
+fn test_restart_policy_always() {
+    init();
+
+    // The subprocess exits after every frame, so it has to be restarted to reach EOS
+    let pipeline = build_pipeline("head -c 1000 > /dev/null", 5);
+    let sink = pipeline.iterate_sinks().next().unwrap().unwrap();
+    sink.set_property_from_str("restart-policy", "always");
+    sink.set_property("restart-backoff", 10_000_000u64);
+
+    pipeline.set_state(gst::State::Playing).expect("Failed to set pipeline to Playing");
+
+    let bus = pipeline.bus().unwrap();
+    let mut restarts = 0;
+    let result = loop {
+        let msg = bus
+            .timed_pop_filtered(
+                gst::ClockTime::from_seconds(10),
+                &[gst::MessageType::Eos, gst::MessageType::Error, gst::MessageType::Element],
+            )
+            .expect("No EOS or Error message received within timeout");
+
+        match msg.view() {
+            gst::MessageView::Element(elem) => {
+                let s = elem.structure().unwrap();
+                if s.name() == "subprocess-restarted" {
+                    restarts += 1;
+                    assert_eq!(s.get::<u32>("restart-count").unwrap(), restarts);
+                }
+            }
+            gst::MessageView::Eos(..) => break Ok(()),
+            gst::MessageView::Error(err) => break Err(err.error()),
+            _ => unreachable!(),
+        }
+    };
+
+    pipeline.set_state(gst::State::Null).expect("Failed to set pipeline to Null");
+
+    result.expect("Error from pipeline");
+    assert!(restarts > 0, "Expected the subprocess to be restarted");
+}
+
+#[test]
+#[serial]
+fn test_restart_backoff_interrupted() {
+    init();
+
+    // The subprocess exits right away and the streaming thread waits in the long backoff
+    let pipeline = build_pipeline("exit 0", 5);
+    let sink = pipeline.iterate_sinks().next().unwrap().unwrap();
+    sink.set_property_from_str("restart-policy", "always");
+    sink.set_property("restart-backoff", 30_000_000_000u64);
+
+    pipeline.set_state(gst::State::Playing).expect("Failed to set pipeline to Playing");
+    thread::sleep(Duration::from_millis(500));
+
+    // Neither property reads nor stopping wait for the backoff
+    let start = Instant::now();
+    let _ = sink.property::<gst::Structure>("stats");
+    let _ = sink.property::<u64>("restart-backoff");
+    pipeline.set_state(gst::State::Null).expect("Failed to set pipeline to Null");
+    assert!(start.elapsed() < Duration::from_secs(5), "Backoff blocked for {:?}", start.elapsed());
+}
+
+#[test]
+#[serial]
+fn test_restart_with_default_sigpipe() {
+    extern "C" {
+        fn signal(signum: i32, handler: usize) -> usize;
+    }
+    const SIGPIPE: i32 = 13;
+    const SIG_DFL: usize = 0;
+
+    init();
+
+    // The test harness ignores SIGPIPE, C applications like gst-launch-1.0 don't. Writing to
+    // the exited subprocess must not kill the process.
+    let previous = unsafe { signal(SIGPIPE, SIG_DFL) };
+
+    let pipeline = build_pipeline("head -c 1000 > /dev/null", 5);
+    let sink = pipeline.iterate_sinks().next().unwrap().unwrap();
+    sink.set_property_from_str("restart-policy", "always");
+    sink.set_property("restart-backoff", 10_000_000u64);
+
+    pipeline.set_state(gst::State::Playing).expect("Failed to set pipeline to Playing");
+    let msg = wait_for_message(
+        &pipeline,
+        gst::ClockTime::from_seconds(10),
+        &[gst::MessageType::Eos, gst::MessageType::Error],
+    );
+    pipeline.set_state(gst::State::Null).expect("Failed to set pipeline to Null");
+
+    unsafe { signal(SIGPIPE, previous) };
+    assert!(matches!(msg.as_ref().map(|msg| msg.view()), Some(gst::MessageView::Eos(..))));
+}
+
+#[test]
+#[serial]
+fn test_restart_policy_max_restarts() {
+    init();
+
+    let pipeline = build_pipeline("head -c 1000 > /dev/null; exit 1", 20);
+    let sink = pipeline.iterate_sinks().next().unwrap().unwrap();
+    sink.set_property_from_str("restart-policy", "on-failure");
+    sink.set_property("max-restarts", 2i32);
+    sink.set_property("restart-backoff", 10_000_000u64);
+
+    pipeline.set_state(gst::State::Playing).expect("Failed to set pipeline to Playing");
+
+    let msg = wait_for_message(
+        &pipeline,
+        gst::ClockTime::from_seconds(10),
+        &[gst::MessageType::Eos, gst::MessageType::Error],
+    );
+
+    pipeline.set_state(gst::State::Null).expect("Failed to set pipeline to Null");
+
+    assert!(
+        matches!(msg.as_ref().map(|msg| msg.view()), Some(gst::MessageView::Error(..))),
+        "Expected an error once the restarts are exhausted"
+    );
+}
+
+#[test]
+#[serial]
+fn test_stop_escalates_to_sigkill() {
+    init();
+
+    // The subprocess ignores SIGTERM and keeps running after stdin is closed
+    let pipeline = build_pipeline("trap '' TERM; cat > /dev/null; exec sleep 30", 1);
+    let sink = pipeline.iterate_sinks().next().unwrap().unwrap();
+    sink.set_property("wait-for-exit", 50_000_000u64);
+    sink.set_property("kill-timeout", 200_000_000u64);
+    sink.set_property("eos-timeout", 100_000_000u64);
+
+    pipeline.set_state(gst::State::Playing).expect("Failed to set pipeline to Playing");
+
+    let msg = wait_for_message(
+        &pipeline,
+        gst::ClockTime::from_seconds(5),
+        &[gst::MessageType::Eos, gst::MessageType::Error],
+    );
+    assert!(matches!(msg.as_ref().map(|msg| msg.view()), Some(gst::MessageView::Eos(..))));
+
+    let start_time = Instant::now();
+    pipeline.set_state(gst::State::Ready).expect("Failed to set pipeline to Ready");
+    let elapsed = start_time.elapsed();
+
+    let bus = pipeline.bus().unwrap();
+    let steps: Vec<String> = std::iter::from_fn(|| bus.pop_filtered(&[gst::MessageType::Element]))
+        .filter_map(|msg| {
+            let s = msg.structure()?;
+            (s.name() == "subprocess-stopping").then(|| s.get::<String>("step").unwrap())
+        })
+        .collect();
+
+    pipeline.set_state(gst::State::Null).expect("Failed to set pipeline to Null");
+
+    assert!(elapsed < Duration::from_secs(5), "Stopping took too long: {:?}", elapsed);
+    assert_eq!(steps, ["stdin-closed", "signal", "kill"]);
+}
+
+// Like wait_for_message, but returns every message popped on the way
+fn collect_messages(
+    pipeline: &gst::Pipeline,
+    timeout: gst::ClockTime,
+    msg_types: &[gst::MessageType],
+) -> Vec<gst::Message> {
+    let bus = pipeline.bus().unwrap();
+    let deadline = Instant::now() + Duration::from(timeout);
+    let mut messages = Vec::new();
+
+    while let Some(msg) = bus.timed_pop(
+        gst::ClockTime::try_from(deadline.saturating_duration_since(Instant::now())).unwrap(),
+    ) {
+        let done = msg_types.contains(&msg.type_());
+        messages.push(msg);
+        if done {
+            break;
+        }
+    }
+
+    messages
+}
+
+// Returns the structures of all element messages with the given name
+fn element_messages(messages: &[gst::Message], name: &str) -> Vec<gst::Structure> {
+    messages
+        .iter()
+        .filter_map(|msg| match msg.view() {
+            gst::MessageView::Element(elem) => elem.structure().map(|s| s.to_owned()),
+            _ => None,
+        })
+        .filter(|s| s.name() == name)
+        .collect()
+}
+
+#[test]
+#[serial]
+fn test_exit_status_reporting() {
+    init();
+
+    for error_on_nonzero_exit in [false, true] {
+        let pipeline = build_pipeline("cat > /dev/null; exit 3", 1);
+        let sink = pipeline.iterate_sinks().next().unwrap().unwrap();
+        sink.set_property("error-on-nonzero-exit", error_on_nonzero_exit);
+
+        pipeline.set_state(gst::State::Playing).expect("Failed to set pipeline to Playing");
+
+        // The subprocess is reaped on EOS, and a non-zero exit replaces EOS with an error
+        let messages = collect_messages(
+            &pipeline,
+            gst::ClockTime::from_seconds(5),
+            &[gst::MessageType::Eos, gst::MessageType::Error],
+        );
+        let expected = if error_on_nonzero_exit { gst::MessageType::Error } else { gst::MessageType::Eos };
+        assert_eq!(messages.last().map(|msg| msg.type_()), Some(expected));
+
+        pipeline.set_state(gst::State::Null).expect("Failed to set pipeline to Null");
+
+        let exited = element_messages(&messages, "subprocess-exited");
+        assert_eq!(exited.len(), 1);
+        let s = &exited[0];
+        assert_eq!(s.get::<i32>("exit-code").unwrap(), 3);
+        assert_eq!(s.get::<i32>("signal").unwrap(), 0);
+        assert!(!s.get::<bool>("core-dumped").unwrap());
+        assert!(s.get::<u32>("pid").unwrap() > 0);
+    }
+}
+
+#[test]
+#[serial]
+fn test_eos_waits_for_subprocess() {
+    init();
+
+    // The subprocess only finishes its output a while after stdin is closed
+    let temp_file = create_temp_filepath("txt");
+    let cmd = format!("cat > /dev/null; sleep 0.5; echo done > {}", temp_file);
+    let pipeline = build_pipeline(&cmd, 1);
+
+    pipeline.set_state(gst::State::Playing).expect("Failed to set pipeline to Playing");
+
+    let msg = wait_for_message(
+        &pipeline,
+        gst::ClockTime::from_seconds(5),
+        &[gst::MessageType::Eos, gst::MessageType::Error],
+    );
+
+    // Check the output before tearing down the pipeline
+    let output = fs::read_to_string(&temp_file);
+
+    pipeline.set_state(gst::State::Null).expect("Failed to set pipeline to Null");
+
+    match msg.as_ref().map(|msg| msg.view()) {
+        Some(gst::MessageView::Eos(..)) => {}
+        Some(gst::MessageView::Error(err)) => panic!("Error from pipeline: {}", err.error()),
+        _ => panic!("No EOS or Error message received within timeout"),
+    }
+
+    assert_eq!(output.expect("Output not written before EOS").trim(), "done");
+
+    fs::remove_file(temp_file).ok();
+}
+
+#[test]
+#[serial]
+fn test_state_change_during_eos_drain() {
+    init();
+
+    // The subprocess keeps running long after stdin is closed
+    let pipeline = build_pipeline("cat > /dev/null; exec sleep 30", 1);
+    pipeline.set_state(gst::State::Playing).expect("Failed to set pipeline to Playing");
+    thread::sleep(Duration::from_millis(500));
+
+    // Stopping interrupts the wait for the subprocess to exit after EOS
+    let start = Instant::now();
+    pipeline.set_state(gst::State::Null).expect("Failed to set pipeline to Null");
+    assert!(start.elapsed() < Duration::from_secs(5), "Stopping took {:?}", start.elapsed());
+}
+
+#[test]
+#[serial]
+fn test_state_change_with_stuck_consumer() {
+    init();
+
+    // The subprocess never reads stdin, so the pipe fills up and render() blocks
+    let pipeline = build_pipeline("exec sleep 30", 100);
+
+    pipeline.set_state(gst::State::Playing).expect("Failed to set pipeline to Playing");
+    thread::sleep(Duration::from_millis(500));
+
+    // unlock() has to interrupt the blocked write for PAUSED->READY to complete
+    let start_time = Instant::now();
+    pipeline.set_state(gst::State::Null).expect("Failed to set pipeline to Null");
+    let elapsed = start_time.elapsed();
+
+    assert!(elapsed < Duration::from_secs(5), "State change blocked for {:?}", elapsed);
+}
+
+#[test]
+#[serial]
+fn test_flushing_seek_with_stuck_consumer() {
+    init();
+
+    let pipeline = build_pipeline("exec sleep 30", 100);
+
+    pipeline.set_state(gst::State::Playing).expect("Failed to set pipeline to Playing");
+    thread::sleep(Duration::from_millis(500));
+
+    // The flush has to interrupt the blocked write, or the seek never returns
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let seek_pipeline = pipeline.clone();
+    thread::spawn(move || {
+        let result = seek_pipeline.seek_simple(gst::SeekFlags::FLUSH, gst::ClockTime::ZERO);
+        let _ = sender.send(result);
+    });
+
+    let result = receiver.recv_timeout(Duration::from_secs(5));
+
+    pipeline.set_state(gst::State::Null).expect("Failed to set pipeline to Null");
+
+    result.expect("Flushing seek blocked").expect("Seek failed");
+}
+
+#[test]
+#[serial]
+fn test_seek_after_eos() {
+    init();
+
+    let output_path = create_temp_filepath(".raw");
+    let (pipeline, _sink) = build_small_frames_pipeline(&format!("cat >> {}", output_path), -1);
+
+    // Plays the first 100 ms, until EOS
+    let play_segment = || {
+        pipeline
+            .seek(
+                1.0,
+                gst::SeekFlags::FLUSH | gst::SeekFlags::ACCURATE,
+                gst::SeekType::Set,
+                gst::ClockTime::ZERO,
+                gst::SeekType::Set,
+                gst::ClockTime::from_mseconds(100),
+            )
+            .expect("Seek failed");
+        let msg = wait_for_message(
+            &pipeline,
+            gst::ClockTime::from_seconds(5),
+            &[gst::MessageType::Eos, gst::MessageType::Error],
+        );
+        assert!(matches!(msg.as_ref().map(|msg| msg.view()), Some(gst::MessageView::Eos(..))));
+    };
+
+    pipeline.set_state(gst::State::Paused).expect("Failed to set pipeline to Paused");
+    pipeline.state(gst::ClockTime::from_seconds(5)).0.expect("Failed to preroll");
+    pipeline.set_state(gst::State::Playing).expect("Failed to set pipeline to Playing");
+    play_segment();
+    let first = fs::metadata(&output_path).expect("Failed to stat output").len();
+    assert!(first > 0);
+
+    // The drained subprocess is spawned again for the frames after the seek
+    play_segment();
+    pipeline.set_state(gst::State::Null).expect("Failed to set pipeline to Null");
+
+    let output = fs::metadata(&output_path).expect("Failed to stat output");
+    assert_eq!(output.len(), 2 * first);
+
+    fs::remove_file(&output_path).ok();
+}
+
+// Builds a pipeline with small GRAY8 frames, which are written atomically to the pipe
+fn build_small_frames_pipeline(cmd: &str, num_buffers: i32) -> (gst::Pipeline, gst::Element) {
+    let pipeline = gst::Pipeline::new();
+
+    let src = gst::ElementFactory::make("videotestsrc")
+        .build()
+        .expect("Failed to create videotestsrc");
+    src.set_property("num-buffers", num_buffers);
+
+    let capsfilter = gst::ElementFactory::make("capsfilter")
+        .build()
+        .expect("Failed to create capsfilter");
+    let caps = gst::Caps::builder("video/x-raw")
+        .field("format", "GRAY8")
+        .field("width", 64i32)
+        .field("height", 64i32)
+        .build();
+    capsfilter.set_property("caps", caps);
+
+    let sink = gst::ElementFactory::make("videopipesink")
+        .build()
+        .expect("Failed to create videopipesink");
+    sink.set_property("cmd", cmd);
+
+    pipeline.add_many(&[&src, &capsfilter, &sink]).unwrap();
+    gst::Element::link_many(&[&src, &capsfilter, &sink]).expect("Failed to link elements");
+
+    (pipeline, sink)
+}
+
+#[test]
+#[serial]
+fn test_stall_action_drop() {
+    init();
+
+    // 64 KiB of pipe buffer fit 16 frames, the rest has to be dropped
+    let (pipeline, sink) = build_small_frames_pipeline("exec sleep 30", 30);
+    sink.set_property("write-timeout", 20_000_000u64);
+    sink.set_property_from_str("stall-action", "drop");
+    sink.set_property("eos-timeout", 100_000_000u64);
+
+    pipeline.set_state(gst::State::Playing).expect("Failed to set pipeline to Playing");
+
+    let messages = collect_messages(
+        &pipeline,
+        gst::ClockTime::from_seconds(5),
+        &[gst::MessageType::Eos, gst::MessageType::Error],
+    );
+
+    pipeline.set_state(gst::State::Null).expect("Failed to set pipeline to Null");
+
+    assert_eq!(messages.last().map(|msg| msg.type_()), Some(gst::MessageType::Eos));
+
+    let stalls = element_messages(&messages, "subprocess-stalled");
+    assert!(!stalls.is_empty(), "Expected subprocess-stalled messages");
+    for s in &stalls {
+        assert_eq!(s.get::<u64>("bytes-written").unwrap(), 0);
+        assert_eq!(s.get::<u64>("buffer-size").unwrap(), 4096);
+    }
+}
+
+#[test]
+#[serial]
+fn test_stall_action_error() {
+    init();
+
+    let (pipeline, sink) = build_small_frames_pipeline("exec sleep 30", 30);
+    sink.set_property("write-timeout", 20_000_000u64);
+    sink.set_property_from_str("stall-action", "error");
+
+    pipeline.set_state(gst::State::Playing).expect("Failed to set pipeline to Playing");
+
+    let messages = collect_messages(
+        &pipeline,
+        gst::ClockTime::from_seconds(5),
+        &[gst::MessageType::Eos, gst::MessageType::Error],
+    );
+
+    pipeline.set_state(gst::State::Null).expect("Failed to set pipeline to Null");
+
+    assert_eq!(messages.last().map(|msg| msg.type_()), Some(gst::MessageType::Error));
+    assert_eq!(element_messages(&messages, "subprocess-stalled").len(), 1);
+}
+
+#[test]
+#[serial]
+fn test_writer_queue_writes_all_buffers() {
+    init();
+
+    let temp_file = create_temp_filepath("gray");
+    let (pipeline, sink) = build_small_frames_pipeline(&format!("cat > {}", temp_file), 30);
+    sink.set_property("max-queued-buffers", 4u32);
+
+    pipeline.set_state(gst::State::Playing).expect("Failed to set pipeline to Playing");
+
+    let msg = wait_for_message(
+        &pipeline,
+        gst::ClockTime::from_seconds(5),
+        &[gst::MessageType::Eos, gst::MessageType::Error],
+    );
+
+    pipeline.set_state(gst::State::Null).expect("Failed to set pipeline to Null");
+
+    match msg.as_ref().map(|msg| msg.view()) {
+        Some(gst::MessageView::Eos(..)) => {}
+        Some(gst::MessageView::Error(err)) => panic!("Error from pipeline: {}", err.error()),
+        _ => panic!("No EOS or Error message received within timeout"),
+    }
+
+    // The queue is drained on EOS
+    let metadata = fs::metadata(&temp_file).expect("Output file not created");
+    assert_eq!(metadata.len(), 30 * 4096);
+
+    fs::remove_file(temp_file).ok();
+}
+
+#[test]
+#[serial]
+fn test_leaky_writer_queue_with_stuck_consumer() {
+    init();
+
+    // Without a leaky queue the streaming thread would block on the full pipe
+    let (pipeline, sink) = build_small_frames_pipeline("exec sleep 30", 60);
+    sink.set_property("max-queued-buffers", 2u32);
+    sink.set_property_from_str("leaky", "downstream");
+    sink.set_property("eos-timeout", 100_000_000u64);
+
+    let start_time = Instant::now();
+    pipeline.set_state(gst::State::Playing).expect("Failed to set pipeline to Playing");
+
+    let msg = wait_for_message(
+        &pipeline,
+        gst::ClockTime::from_seconds(10),
+        &[gst::MessageType::Eos, gst::MessageType::Error],
+    );
+    let elapsed = start_time.elapsed();
+
+    pipeline.set_state(gst::State::Null).expect("Failed to set pipeline to Null");
+
+    match msg.as_ref().map(|msg| msg.view()) {
+        Some(gst::MessageView::Eos(..)) => {}
+        Some(gst::MessageView::Error(err)) => panic!("Error from pipeline: {}", err.error()),
+        _ => panic!("No EOS or Error message received within timeout"),
+    }
+
+    // 60 frames at 30 fps, plus the EOS timeouts
+    assert!(elapsed < Duration::from_secs(5), "Streaming was blocked: {:?}", elapsed);
+}
+
+#[test]
+#[serial]
+fn test_leaky_writer_queue_keeps_caps_records() {
+    use gstsubprocesspipe::length_prefixed::{Reader, RecordKind};
+
+    init();
+
+    let output_path = create_temp_filepath("records");
+    let sink = gst::ElementFactory::make("videopipesink")
+        .property("cmd", format!("sleep 1; cat > {}", output_path))
+        .property("max-queued-buffers", 1u32)
+        .build()
+        .expect("Failed to create videopipesink");
+    sink.set_property_from_str("leaky", "upstream");
+    sink.set_property_from_str("framing", "length-prefixed");
+    sink.set_property_from_str("on-caps-change", "ignore");
+
+    let pipeline = gst::Pipeline::new();
+    let src = gst::ElementFactory::make("appsrc")
+        .property_from_str("format", "time")
+        .build()
+        .expect("Failed to create appsrc");
+    pipeline.add_many(&[&src, &sink]).unwrap();
+    src.link(&sink).expect("Failed to link elements");
+    pipeline.set_state(gst::State::Playing).expect("Failed to set pipeline to Playing");
+
+    let push = |i: u64, size: i32| {
+        let caps = gst::Caps::builder("video/x-raw")
+            .field("format", "GRAY8")
+            .field("width", size)
+            .field("height", size)
+            .field("framerate", gst::Fraction::new(30, 1))
+            .build();
+        src.set_property("caps", caps);
+
+        let mut buffer = gst::Buffer::from_mut_slice(vec![0u8; (size * size) as usize]);
+        buffer.get_mut().unwrap().set_pts(gst::ClockTime::from_mseconds(33 * i));
+        let _: gst::FlowReturn = src.emit_by_name("push-buffer", &[&buffer]);
+    };
+
+    // The first frame fills the pipe and the second the queue, so the first frame with the new
+    // caps is dropped together with the caps record in front of it
+    for i in 0..3 {
+        push(i, 256);
+    }
+    push(3, 128);
+    thread::sleep(Duration::from_secs(2));
+    push(4, 128);
+    push(5, 128);
+    let _: gst::FlowReturn = src.emit_by_name("end-of-stream", &[]);
+
+    let msg = wait_for_message(
+        &pipeline,
+        gst::ClockTime::from_seconds(5),
+        &[gst::MessageType::Eos, gst::MessageType::Error],
+    );
+    assert!(matches!(msg.as_ref().map(|msg| msg.view()), Some(gst::MessageView::Eos(..))));
+    pipeline.set_state(gst::State::Null).expect("Failed to set pipeline to Null");
+
+    // Every buffer record comes after the caps record of its caps
+    let records = Reader::new(File::open(&output_path).expect("Failed to open records"))
+        .collect::<Result<Vec<_>, _>>()
+        .expect("Failed to read records");
+    let mut caps_seqs = Vec::new();
+    for record in &records {
+        match record.header.kind {
+            RecordKind::Caps => caps_seqs.push(record.header.caps_seq),
+            _ => assert!(caps_seqs.contains(&record.header.caps_seq), "No caps record for {:?}", record.header),
+        }
+    }
+    assert_eq!(caps_seqs.len(), 2);
+    assert_eq!(records.last().unwrap().payload.len(), 128 * 128);
+
+    fs::remove_file(&output_path).ok();
+}
+
+#[test]
+#[serial]
+fn test_stats_property() {
+    init();
+
+    let (pipeline, sink) = build_small_frames_pipeline("cat > /dev/null", 10);
+
+    pipeline.set_state(gst::State::Playing).expect("Failed to set pipeline to Playing");
+
+    // Stats can be queried while streaming
+    thread::sleep(Duration::from_millis(100));
+    let stats = sink.property::<gst::Structure>("stats");
+    assert!(stats.get::<u32>("pid").unwrap() > 0);
+
+    let msg = wait_for_message(
+        &pipeline,
+        gst::ClockTime::from_seconds(5),
+        &[gst::MessageType::Eos, gst::MessageType::Error],
+    );
+    assert!(matches!(msg.as_ref().map(|msg| msg.view()), Some(gst::MessageView::Eos(..))));
+
+    let stats = sink.property::<gst::Structure>("stats");
+    assert_eq!(stats.name(), "application/x-videopipesink-stats");
+    assert_eq!(stats.get::<u64>("buffers-written").unwrap(), 10);
+    assert_eq!(stats.get::<u64>("bytes-written").unwrap(), 10 * 4096);
+    assert_eq!(stats.get::<u64>("buffers-dropped").unwrap(), 0);
+    assert_eq!(stats.get::<u32>("restarts").unwrap(), 0);
+    let total = stats.get::<gst::ClockTime>("write-latency-total").unwrap();
+    let max = stats.get::<gst::ClockTime>("write-latency-max").unwrap();
+    assert!(max <= total);
+
+    // The subprocess was reaped on EOS
+    assert_eq!(stats.get::<u32>("pid").unwrap(), 0);
+
+    // Stats are reset when the element starts again
+    pipeline.set_state(gst::State::Null).expect("Failed to set pipeline to Null");
+    pipeline.set_state(gst::State::Paused).expect("Failed to set pipeline to Paused");
+    let stats = sink.property::<gst::Structure>("stats");
+    assert!(stats.get::<u64>("buffers-written").unwrap() <= 1);
+
+    pipeline.set_state(gst::State::Null).expect("Failed to set pipeline to Null");
+}
+
+#[test]
+#[serial]
+fn test_y4m_framing() {
+    init();
+
+    let output_path = create_temp_filepath(".y4m");
+    let (pipeline, sink) = build_small_frames_pipeline(&format!("cat > {}", output_path), 3);
+    sink.set_property_from_str("framing", "y4m");
+
+    pipeline.set_state(gst::State::Playing).expect("Failed to set pipeline to Playing");
+    let msg = wait_for_message(
+        &pipeline,
+        gst::ClockTime::from_seconds(5),
+        &[gst::MessageType::Eos, gst::MessageType::Error],
+    );
+    assert!(matches!(msg.as_ref().map(|msg| msg.view()), Some(gst::MessageView::Eos(..))));
+    pipeline.set_state(gst::State::Null).expect("Failed to set pipeline to Null");
+
+    let header = b"YUV4MPEG2 W64 H64 F30:1 Ip A1:1 Cmono\n";
+    let frame_size = b"FRAME\n".len() + 64 * 64;
+
+    let output = fs::read(&output_path).expect("Failed to read output");
+    assert!(output.starts_with(header));
+    assert_eq!(output.len(), header.len() + 3 * frame_size);
+    for frame in output[header.len()..].chunks(frame_size) {
+        assert!(frame.starts_with(b"FRAME\n"));
+    }
+
+    fs::remove_file(&output_path).ok();
+}
+
+#[test]
+#[serial]
+fn test_y4m_framing_packs_planes() {
+    init();
+
+    // Padded strides would not match the frame size the Y4M header declares
+    let output_path = create_temp_filepath(".y4m");
+    let pipeline = gst::parse::launch(&format!(
+        "videotestsrc num-buffers=2 ! video/x-raw,format=I420,width=33,height=10 ! \
+         videopipesink framing=y4m pack-planes=false cmd=\"cat > {}\"",
+        output_path
+    ))
+    .expect("Failed to create pipeline")
+    .downcast::<gst::Pipeline>()
+    .unwrap();
+
+    pipeline.set_state(gst::State::Playing).expect("Failed to set pipeline to Playing");
+    let msg = wait_for_message(
+        &pipeline,
+        gst::ClockTime::from_seconds(5),
+        &[gst::MessageType::Eos, gst::MessageType::Error],
+    );
+    assert!(matches!(msg.as_ref().map(|msg| msg.view()), Some(gst::MessageView::Eos(..))));
+    pipeline.set_state(gst::State::Null).expect("Failed to set pipeline to Null");
+
+    let header_len = fs::read(&output_path)
+        .expect("Failed to read output")
+        .iter()
+        .position(|byte| *byte == b'\n')
+        .unwrap()
+        + 1;
+    let packed_size = 33 * 10 + 2 * 17 * 5;
+    let output = fs::metadata(&output_path).expect("Failed to stat output");
+    assert_eq!(output.len(), (header_len + 2 * (b"FRAME\n".len() + packed_size)) as u64);
+
+    fs::remove_file(&output_path).ok();
+}
+
+#[test]
+#[serial]
+fn test_y4m_framing_unsupported_format() {
+    init();
+
+    let pipeline = gst::parse::launch(
+        "videotestsrc num-buffers=1 ! video/x-raw,format=RGB,width=64,height=64 ! \
+         videopipesink name=sink framing=y4m cmd=\"cat > /dev/null\"",
+    )
+    .expect("Failed to create pipeline")
+    .downcast::<gst::Pipeline>()
+    .unwrap();
+
+    pipeline.set_state(gst::State::Playing).expect("Failed to set pipeline to Playing");
+    let msg = wait_for_message(
+        &pipeline,
+        gst::ClockTime::from_seconds(5),
+        &[gst::MessageType::Eos, gst::MessageType::Error],
+    );
+    match msg.as_ref().map(|msg| msg.view()) {
+        Some(gst::MessageView::Error(err)) => {
+            assert!(err.error().matches(gst::CoreError::Negotiation));
+        }
+        other => panic!("Expected a negotiation error, got {:?}", other),
+    }
+
+    pipeline.set_state(gst::State::Null).expect("Failed to set pipeline to Null");
+}
+
+#[test]
+#[serial]
+fn test_length_prefixed_framing() {
+    use gstsubprocesspipe::length_prefixed::{Reader, RecordKind};
+
+    init();
+
+    let sink = gst::ElementFactory::make("videopipesink")
+        .build()
+        .expect("Failed to create videopipesink");
+
+    let output_path = create_temp_filepath(".bin");
+    sink.set_property("cmd", format!("cat > {}", output_path));
+    sink.set_property_from_str("framing", "length-prefixed");
+
+    // The subprocess keeps running across the caps change and gets a new caps record
+    let msg = run_caps_change_pipeline(&sink, &[(64, 48), (32, 24)]);
+    assert!(matches!(msg.as_ref().map(|msg| msg.view()), Some(gst::MessageView::Eos(..))));
+
+    let file = File::open(&output_path).expect("Failed to open output");
+    let records = Reader::new(file)
+        .collect::<Result<Vec<_>, _>>()
+        .expect("Failed to read records");
+    assert_eq!(records.len(), 4);
+
+    for (i, &(width, height)) in [(64, 48), (32, 24)].iter().enumerate() {
+        let caps_seq = i as u32 + 1;
+
+        let caps_record = &records[i * 2];
+        assert_eq!(caps_record.header.kind, RecordKind::Caps);
+        assert_eq!(caps_record.header.caps_seq, caps_seq);
+        let caps = caps_record.caps().unwrap().parse::<gst::Caps>().unwrap();
+        let s = caps.structure(0).unwrap();
+        assert_eq!(s.get::<i32>("width").unwrap(), width);
+        assert_eq!(s.get::<i32>("height").unwrap(), height);
+
+        let buffer_record = &records[i * 2 + 1];
+        assert_eq!(buffer_record.header.kind, RecordKind::Buffer);
+        assert_eq!(buffer_record.header.caps_seq, caps_seq);
+        assert_eq!(buffer_record.header.length, (width * height) as u64);
+        assert_eq!(buffer_record.payload.len(), (width * height) as usize);
+        assert_eq!(buffer_record.header.pts, Some(gst::ClockTime::from_mseconds(33 * i as u64)));
+    }
+
+    fs::remove_file(&output_path).ok();
+}
+
+#[test]
+#[serial]
+fn test_length_prefixed_invalid_length() {
+    use gstsubprocesspipe::length_prefixed::{Header, Reader, MAX_RECORD_SIZE};
+
+    init();
+
+    // A length above the maximum is rejected without allocating it
+    let mut header = Header::for_caps(0, 1);
+    header.length = u64::MAX;
+    let err = Reader::new(&header.to_bytes()[..]).read_record().unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+    // A stream that ends before the declared length is a short read
+    header.length = MAX_RECORD_SIZE;
+    let mut data = header.to_bytes().to_vec();
+    data.extend_from_slice(b"caps");
+    let err = Reader::new(&data[..]).read_record().unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+}
+
+#[test]
+#[serial]
+fn test_pack_planes() {
+    init();
+
+    // Default strides are padded to a multiple of 4 bytes: 36 for Y and 20 for U and V
+    let padded_size = 36 * 10 + 2 * 20 * 5;
+    let packed_size = 33 * 10 + 2 * 17 * 5;
+
+    for (pack_planes, frame_size) in [(true, packed_size), (false, padded_size)] {
+        let output_path = create_temp_filepath(".yuv");
+        let pipeline = gst::parse::launch(&format!(
+            "videotestsrc num-buffers=2 ! video/x-raw,format=I420,width=33,height=10 ! \
+             videopipesink pack-planes={} cmd=\"cat > {}\"",
+            pack_planes, output_path
+        ))
+        .expect("Failed to create pipeline")
+        .downcast::<gst::Pipeline>()
+        .unwrap();
+
+        pipeline.set_state(gst::State::Playing).expect("Failed to set pipeline to Playing");
+        let msg = wait_for_message(
+            &pipeline,
+            gst::ClockTime::from_seconds(5),
+            &[gst::MessageType::Eos, gst::MessageType::Error],
+        );
+        assert!(matches!(msg.as_ref().map(|msg| msg.view()), Some(gst::MessageView::Eos(..))));
+        pipeline.set_state(gst::State::Null).expect("Failed to set pipeline to Null");
+
+        let output = fs::metadata(&output_path).expect("Failed to stat output");
+        assert_eq!(output.len(), 2 * frame_size as u64, "pack-planes={}", pack_planes);
+
+        fs::remove_file(&output_path).ok();
+    }
+}
+
+#[test]
+#[serial]
+fn test_frame_size_placeholder_odd_width() {
+    init();
+
+    // Default strides are padded to a multiple of 4 bytes: 36 for Y and 20 for U and V
+    let padded_size = 36 * 10 + 2 * 20 * 5;
+    let packed_size = 33 * 10 + 2 * 17 * 5;
+
+    for (pack_planes, frame_size) in [(true, packed_size), (false, padded_size)] {
+        let output_path = create_temp_filepath("txt");
+        let pipeline = gst::parse::launch(&format!(
+            "videotestsrc num-buffers=2 ! video/x-raw,format=I420,width=33,height=10 ! \
+             videopipesink pack-planes={} cmd=\"echo {{frame_size}} > {}; cat > /dev/null\"",
+            pack_planes, output_path
+        ))
+        .expect("Failed to create pipeline")
+        .downcast::<gst::Pipeline>()
+        .unwrap();
+
+        pipeline.set_state(gst::State::Playing).expect("Failed to set pipeline to Playing");
+        let msg = wait_for_message(
+            &pipeline,
+            gst::ClockTime::from_seconds(5),
+            &[gst::MessageType::Eos, gst::MessageType::Error],
+        );
+        assert!(matches!(msg.as_ref().map(|msg| msg.view()), Some(gst::MessageView::Eos(..))));
+        pipeline.set_state(gst::State::Null).expect("Failed to set pipeline to Null");
+
+        let output = fs::read_to_string(&output_path).expect("Failed to read output");
+        assert_eq!(output.trim(), frame_size.to_string(), "pack-planes={}", pack_planes);
+
+        fs::remove_file(&output_path).ok();
+    }
+}
+
+#[test]
+#[serial]
+fn test_caps_property() {
+    init();
+
+    let output_path = create_temp_filepath(".raw");
+    let pipeline = gst::parse::launch(&format!(
+        "videotestsrc num-buffers=2 ! videoconvert ! \
+         videopipesink name=sink caps=\"video/x-raw,format=GRAY8,width=16,height=16\" cmd=\"cat > {}\"",
+        output_path
+    ))
+    .expect("Failed to create pipeline")
+    .downcast::<gst::Pipeline>()
+    .unwrap();
+
+    let sink = pipeline.by_name("sink").unwrap();
+    let sink_pad = sink.static_pad("sink").unwrap();
+
+    let accepted = gst::Caps::builder("video/x-raw")
+        .field("format", "GRAY8")
+        .field("width", 16i32)
+        .field("height", 16i32)
+        .field("framerate", gst::Fraction::new(30, 1))
+        .build();
+    assert!(sink_pad.query_accept_caps(&accepted));
+
+    let rejected = gst::Caps::builder("video/x-raw")
+        .field("format", "RGB")
+        .field("width", 16i32)
+        .field("height", 16i32)
+        .field("framerate", gst::Fraction::new(30, 1))
+        .build();
+    assert!(!sink_pad.query_accept_caps(&rejected));
+
+    // videoconvert and videotestsrc negotiate the restricted caps without a capsfilter
+    pipeline.set_state(gst::State::Playing).expect("Failed to set pipeline to Playing");
+    let msg = wait_for_message(
+        &pipeline,
+        gst::ClockTime::from_seconds(5),
+        &[gst::MessageType::Eos, gst::MessageType::Error],
+    );
+    assert!(matches!(msg.as_ref().map(|msg| msg.view()), Some(gst::MessageView::Eos(..))));
+    pipeline.set_state(gst::State::Null).expect("Failed to set pipeline to Null");
+
+    let output = fs::metadata(&output_path).expect("Failed to stat output");
+    assert_eq!(output.len(), 2 * 16 * 16);
+
+    fs::remove_file(&output_path).ok();
+}
+
+#[test]
+#[serial]
+fn test_audiopipesink_templating_and_wav() {
+    init();
+
+    let vars_path = create_temp_filepath(".txt");
+    let output_path = create_temp_filepath(".wav");
+    let pipeline = gst::parse::launch(&format!(
+        "audiotestsrc num-buffers=2 samplesperbuffer=100 ! audio/x-raw,format=S16LE,rate=8000,channels=2 ! \
+         audiopipesink framing=wav \
+         cmd=\"echo {{rate}} {{channels}} {{format}} {{ffmpeg_format}} {{ffmpeg_sample_fmt}} > {}; cat > {}\"",
+        vars_path, output_path
+    ))
+    .expect("Failed to create pipeline")
+    .downcast::<gst::Pipeline>()
+    .unwrap();
+
+    pipeline.set_state(gst::State::Playing).expect("Failed to set pipeline to Playing");
+    let msg = wait_for_message(
+        &pipeline,
+        gst::ClockTime::from_seconds(5),
+        &[gst::MessageType::Eos, gst::MessageType::Error],
+    );
+    assert!(matches!(msg.as_ref().map(|msg| msg.view()), Some(gst::MessageView::Eos(..))));
+    pipeline.set_state(gst::State::Null).expect("Failed to set pipeline to Null");
+
+    let vars = fs::read_to_string(&vars_path).expect("Failed to read variables");
+    assert_eq!(vars.trim(), "8000 2 S16LE s16le s16");
+
+    let output = fs::read(&output_path).expect("Failed to read output");
+    assert_eq!(output.len(), 44 + 2 * 100 * 4);
+    assert_eq!(&output[0..4], b"RIFF");
+    assert_eq!(&output[8..16], b"WAVEfmt ");
+    assert_eq!(u16::from_le_bytes([output[20], output[21]]), 1);
+    assert_eq!(u16::from_le_bytes([output[22], output[23]]), 2);
+    assert_eq!(u32::from_le_bytes(output[24..28].try_into().unwrap()), 8000);
+    assert_eq!(u16::from_le_bytes([output[34], output[35]]), 16);
+    assert_eq!(&output[36..40], b"data");
+
+    fs::remove_file(&vars_path).ok();
+    fs::remove_file(&output_path).ok();
+}
+
+#[test]
+#[serial]
+fn test_audiopipesink_interleaves_samples() {
+    init();
+
+    let output_path = create_temp_filepath(".raw");
+    let pipeline = gst::Pipeline::new();
+
+    let src = gst::ElementFactory::make("appsrc")
+        .property_from_str("format", "time")
+        .build()
+        .expect("Failed to create appsrc");
+    let caps = gst::Caps::builder("audio/x-raw")
+        .field("format", "S16LE")
+        .field("rate", 8000i32)
+        .field("channels", 2i32)
+        .field("layout", "non-interleaved")
+        .build();
+    src.set_property("caps", caps);
+
+    let sink = gst::ElementFactory::make("audiopipesink")
+        .build()
+        .expect("Failed to create audiopipesink");
+    sink.set_property("cmd", format!("cat > {}", output_path));
+
+    pipeline.add_many(&[&src, &sink]).unwrap();
+    src.link(&sink).expect("Failed to link elements");
+    pipeline.set_state(gst::State::Playing).expect("Failed to set pipeline to Playing");
+
+    // Two samples of the first channel, then two of the second
+    let mut buffer = gst::Buffer::from_mut_slice(vec![1u8, 1, 1, 1, 2, 2, 2, 2]);
+    buffer.get_mut().unwrap().set_pts(gst::ClockTime::ZERO);
+    let _: gst::FlowReturn = src.emit_by_name("push-buffer", &[&buffer]);
+    let _: gst::FlowReturn = src.emit_by_name("end-of-stream", &[]);
+
+    let msg = wait_for_message(
+        &pipeline,
+        gst::ClockTime::from_seconds(5),
+        &[gst::MessageType::Eos, gst::MessageType::Error],
+    );
+    assert!(matches!(msg.as_ref().map(|msg| msg.view()), Some(gst::MessageView::Eos(..))));
+    pipeline.set_state(gst::State::Null).expect("Failed to set pipeline to Null");
+
+    let output = fs::read(&output_path).expect("Failed to read output");
+    assert_eq!(output, [1, 1, 2, 2, 1, 1, 2, 2]);
+
+    fs::remove_file(&output_path).ok();
+}
+
+#[test]
+#[serial]
+fn test_audiopipesink_interleaves_audio_meta_planes() {
+    init();
+
+    let output_path = create_temp_filepath(".raw");
+    let pipeline = gst::Pipeline::new();
+
+    let info = gst_audio::AudioInfo::builder(gst_audio::AudioFormat::S16le, 8000, 2)
+        .layout(gst_audio::AudioLayout::NonInterleaved)
+        .build()
+        .unwrap();
+    let src = gst::ElementFactory::make("appsrc")
+        .property_from_str("format", "time")
+        .build()
+        .expect("Failed to create appsrc");
+    src.set_property("caps", info.to_caps().unwrap());
+
+    let sink = gst::ElementFactory::make("audiopipesink")
+        .build()
+        .expect("Failed to create audiopipesink");
+    sink.set_property("cmd", format!("cat > {}", output_path));
+
+    pipeline.add_many(&[&src, &sink]).unwrap();
+    src.link(&sink).expect("Failed to link elements");
+    pipeline.set_state(gst::State::Playing).expect("Failed to set pipeline to Playing");
+
+    // Two samples of each channel, with padding between the planes that must not be written
+    let mut buffer = gst::Buffer::from_mut_slice(vec![1u8, 1, 1, 1, 9, 9, 9, 9, 2, 2, 2, 2]);
+    {
+        let buffer = buffer.get_mut().unwrap();
+        buffer.set_pts(gst::ClockTime::ZERO);
+        gst_audio::AudioMeta::add(buffer, &info, 2, &[0, 8]).unwrap();
+    }
+    let _: gst::FlowReturn = src.emit_by_name("push-buffer", &[&buffer]);
+    let _: gst::FlowReturn = src.emit_by_name("end-of-stream", &[]);
+
+    let msg = wait_for_message(
+        &pipeline,
+        gst::ClockTime::from_seconds(5),
+        &[gst::MessageType::Eos, gst::MessageType::Error],
+    );
+    assert!(matches!(msg.as_ref().map(|msg| msg.view()), Some(gst::MessageView::Eos(..))));
+    pipeline.set_state(gst::State::Null).expect("Failed to set pipeline to Null");
+
+    let output = fs::read(&output_path).expect("Failed to read output");
+    assert_eq!(output, [1, 1, 2, 2, 1, 1, 2, 2]);
+
+    fs::remove_file(&output_path).ok();
+}
+
+// Run a pipeline ending in an appsink named "sink" and collect all samples until EOS
+fn pull_samples(pipeline: &gst::Pipeline) -> Vec<gst::Sample> {
+    let sink = pipeline.by_name("sink").unwrap();
+    pipeline.set_state(gst::State::Playing).expect("Failed to set pipeline to Playing");
+
+    let mut samples = Vec::new();
+    while let Some(sample) = sink.emit_by_name::<Option<gst::Sample>>("pull-sample", &[]) {
+        samples.push(sample);
+    }
+
+    pipeline.set_state(gst::State::Null).expect("Failed to set pipeline to Null");
+    samples
+}
+
+#[test]
+#[serial]
+fn test_pipesrc_raw_framing() {
+    init();
+
+    let pipeline = gst::parse::launch(
+        "pipesrc cmd=\"head -c 800 /dev/zero\" \
+         caps=\"video/x-raw,format=GRAY8,width=16,height=16,framerate=30/1\" ! \
+         appsink name=sink sync=false",
+    )
+    .expect("Failed to create pipeline")
+    .downcast::<gst::Pipeline>()
+    .unwrap();
+
+    // The trailing 32 bytes are an incomplete frame
+    let samples = pull_samples(&pipeline);
+    assert_eq!(samples.len(), 3);
+
+    for (i, sample) in samples.iter().enumerate() {
+        let buffer = sample.buffer().unwrap();
+        assert_eq!(buffer.size(), 16 * 16);
+        assert_eq!(buffer.pts(), Some(gst::ClockTime::SECOND.mul_div_floor(i as u64, 30).unwrap()));
+    }
+
+    let s = samples[0].caps().unwrap().structure(0).unwrap().to_owned();
+    assert_eq!(s.get::<&str>("format").unwrap(), "GRAY8");
+}
+
+#[test]
+#[serial]
+fn test_pipesrc_raw_framing_requires_caps() {
+    init();
+
+    let src = gst::ElementFactory::make("pipesrc")
+        .property("cmd", "cat /dev/zero")
+        .build()
+        .expect("Failed to create pipesrc");
+
+    assert!(src.set_state(gst::State::Paused).is_err());
+    src.set_state(gst::State::Null).expect("Failed to set element to Null");
+}
+
+#[test]
+#[serial]
+fn test_pipesrc_y4m_framing() {
+    init();
+
+    let pipeline = gst::parse::launch(
+        "pipesrc framing=y4m \
+         cmd=\"printf 'YUV4MPEG2 W4 H2 F25:1 Ip A1:1 Cmono\\nFRAME\\n01234567FRAME\\nabcdefgh'\" ! \
+         appsink name=sink sync=false",
+    )
+    .expect("Failed to create pipeline")
+    .downcast::<gst::Pipeline>()
+    .unwrap();
+
+    let samples = pull_samples(&pipeline);
+    assert_eq!(samples.len(), 2);
+
+    let info = gst_video::VideoInfo::from_caps(samples[0].caps().unwrap()).unwrap();
+    assert_eq!(info.format(), gst_video::VideoFormat::Gray8);
+    assert_eq!((info.width(), info.height()), (4, 2));
+    assert_eq!(info.fps(), gst::Fraction::new(25, 1));
+
+    let frames = samples
+        .iter()
+        .map(|sample| sample.buffer().unwrap().map_readable().unwrap().to_vec())
+        .collect::<Vec<_>>();
+    assert_eq!(frames, [b"01234567".to_vec(), b"abcdefgh".to_vec()]);
+    assert_eq!(samples[1].buffer().unwrap().pts(), Some(gst::ClockTime::from_mseconds(40)));
+}
+
+#[test]
+#[serial]
+fn test_pipesrc_y4m_invalid_tag() {
+    init();
+
+    // A tag starting with a multi-byte character
+    let pipeline = gst::parse::launch(
+        "pipesrc framing=y4m \
+         cmd=\"printf 'YUV4MPEG2 W4 H2 \\303\\251x\\nFRAME\\n01234567'\" ! \
+         fakesink sync=false",
+    )
+    .expect("Failed to create pipeline")
+    .downcast::<gst::Pipeline>()
+    .unwrap();
+
+    pipeline.set_state(gst::State::Playing).expect("Failed to set pipeline to Playing");
+    let msg = wait_for_message(
+        &pipeline,
+        gst::ClockTime::from_seconds(5),
+        &[gst::MessageType::Eos, gst::MessageType::Error],
+    );
+    match msg.as_ref().map(|msg| msg.view()) {
+        Some(gst::MessageView::Error(err)) => {
+            assert!(err.debug().unwrap_or_default().contains("Invalid Y4M tag"));
+        }
+        other => panic!("Expected an error, got {:?}", other),
+    }
+    pipeline.set_state(gst::State::Null).expect("Failed to set pipeline to Null");
+}
+
+#[test]
+#[serial]
+fn test_pipesrc_length_prefixed_round_trip() {
+    init();
+
+    let output_path = create_temp_filepath(".bin");
+    let (pipeline, sink) = build_small_frames_pipeline(&format!("cat > {}", output_path), 3);
+    sink.set_property_from_str("framing", "length-prefixed");
+
+    pipeline.set_state(gst::State::Playing).expect("Failed to set pipeline to Playing");
+    let msg = wait_for_message(
+        &pipeline,
+        gst::ClockTime::from_seconds(5),
+        &[gst::MessageType::Eos, gst::MessageType::Error],
+    );
+    assert!(matches!(msg.as_ref().map(|msg| msg.view()), Some(gst::MessageView::Eos(..))));
+    pipeline.set_state(gst::State::Null).expect("Failed to set pipeline to Null");
+
+    let pipeline = gst::parse::launch(&format!(
+        "pipesrc framing=length-prefixed cmd=\"cat {}\" ! appsink name=sink sync=false",
+        output_path
+    ))
+    .expect("Failed to create pipeline")
+    .downcast::<gst::Pipeline>()
+    .unwrap();
+
+    let samples = pull_samples(&pipeline);
+    assert_eq!(samples.len(), 3);
+
+    for (i, sample) in samples.iter().enumerate() {
+        let info = gst_video::VideoInfo::from_caps(sample.caps().unwrap()).unwrap();
+        assert_eq!((info.width(), info.height()), (64, 64));
+
+        let buffer = sample.buffer().unwrap();
+        assert_eq!(buffer.size(), 64 * 64);
+        assert_eq!(buffer.pts(), Some(gst::ClockTime::SECOND.mul_div_floor(i as u64, 30).unwrap()));
+    }
+
+    fs::remove_file(&output_path).ok();
+}
+
+#[test]
+#[serial]
+fn test_pipefilter_round_trip() {
+    init();
+
+    let launch = |filter: &str| {
+        gst::parse::launch(&format!(
+            "videotestsrc num-buffers=5 pattern=ball ! \
+             video/x-raw,format=GRAY8,width=64,height=64,framerate=30/1 ! {} appsink name=sink sync=false",
+            filter
+        ))
+        .expect("Failed to create pipeline")
+        .downcast::<gst::Pipeline>()
+        .unwrap()
+    };
+
+    let reference = pull_samples(&launch(""));
+    let samples = pull_samples(&launch("pipefilter cmd=cat max-in-flight=2 !"));
+    assert_eq!(reference.len(), 5);
+    assert_eq!(samples.len(), 5);
+
+    // Frames come back in order, with the timestamps of the frames that were written
+    for (i, (sample, reference)) in samples.iter().zip(&reference).enumerate() {
+        let buffer = sample.buffer().unwrap();
+        assert_eq!(buffer.pts(), Some(gst::ClockTime::SECOND.mul_div_floor(i as u64, 30).unwrap()));
+        assert_eq!(
+            buffer.map_readable().unwrap().as_slice(),
+            reference.buffer().unwrap().map_readable().unwrap().as_slice()
+        );
+    }
+}
+
+#[test]
+#[serial]
+fn test_pipefilter_event_order() {
+    init();
+
+    let pipeline = gst::parse::launch(
+        "videotestsrc num-buffers=5 ! video/x-raw,format=GRAY8,width=64,height=64,framerate=30/1 ! \
+         identity name=identity ! pipefilter name=filter cmd=cat max-in-flight=3 ! fakesink sync=false",
+    )
+    .expect("Failed to create pipeline")
+    .downcast::<gst::Pipeline>()
+    .unwrap();
+
+    // A custom serialized event before every buffer
+    let identity_src = pipeline.by_name("identity").unwrap().static_pad("src").unwrap();
+    let count = Arc::new(Mutex::new(0));
+    identity_src.add_probe(gst::PadProbeType::BUFFER, {
+        let count = count.clone();
+        move |pad, _| {
+            let mut count = count.lock().unwrap();
+            let structure = gst::Structure::builder("test-marker").field("index", *count).build();
+            pad.push_event(gst::event::CustomDownstream::new(structure));
+            *count += 1;
+            gst::PadProbeReturn::Ok
+        }
+    });
+
+    let order = Arc::new(Mutex::new(Vec::new()));
+    let filter_src = pipeline.by_name("filter").unwrap().static_pad("src").unwrap();
+    filter_src.add_probe(gst::PadProbeType::BUFFER | gst::PadProbeType::EVENT_DOWNSTREAM, {
+        let order = order.clone();
+        move |_, info| {
+            match &info.data {
+                Some(gst::PadProbeData::Buffer(..)) => order.lock().unwrap().push("buffer"),
+                Some(gst::PadProbeData::Event(event)) if event.type_() == gst::EventType::CustomDownstream => {
+                    order.lock().unwrap().push("marker")
+                }
+                _ => (),
+            }
+            gst::PadProbeReturn::Ok
+        }
+    });
+
+    pipeline.set_state(gst::State::Playing).expect("Failed to set pipeline to Playing");
+    let msg = wait_for_message(
+        &pipeline,
+        gst::ClockTime::from_seconds(5),
+        &[gst::MessageType::Eos, gst::MessageType::Error],
+    );
+    assert!(matches!(msg.as_ref().map(|msg| msg.view()), Some(gst::MessageView::Eos(..))));
+    pipeline.set_state(gst::State::Null).expect("Failed to set pipeline to Null");
+
+    // The events don't overtake the output of the frames written before them
+    assert_eq!(*order.lock().unwrap(), ["marker", "buffer"].repeat(5));
+}
+
+#[test]
+#[serial]
+fn test_pipefilter_latency() {
+    init();
+
+    let pipeline = gst::parse::launch(
+        "videotestsrc is-live=true ! video/x-raw,format=GRAY8,width=64,height=64,framerate=30/1 ! \
+         pipefilter name=filter cmd=cat max-in-flight=3 ! appsink name=sink sync=false",
+    )
+    .expect("Failed to create pipeline")
+    .downcast::<gst::Pipeline>()
+    .unwrap();
+
+    pipeline.set_state(gst::State::Playing).expect("Failed to set pipeline to Playing");
+    let sink = pipeline.by_name("sink").unwrap();
+    assert!(sink.emit_by_name::<Option<gst::Sample>>("pull-sample", &[]).is_some());
+
+    let filter = pipeline.by_name("filter").unwrap();
+    let mut upstream = gst::query::Latency::new();
+    assert!(filter.static_pad("sink").unwrap().peer_query(&mut upstream));
+    let mut query = gst::query::Latency::new();
+    assert!(filter.static_pad("src").unwrap().query(&mut query));
+    pipeline.set_state(gst::State::Null).expect("Failed to set pipeline to Null");
+
+    // Two frames the subprocess may hold back, frame durations are rounded to the nanosecond
+    let (live, min, _) = query.result();
+    assert!(live);
+    let added = (min - upstream.result().1).nseconds();
+    assert!(added.abs_diff(gst::ClockTime::SECOND.nseconds() * 2 / 30) <= 2);
+}
+
+#[test]
+#[serial]
+fn test_pipefilter_output_caps() {
+    init();
+
+    let pipeline = gst::parse::launch(
+        "videotestsrc num-buffers=3 ! video/x-raw,format=GRAY8,width=64,height=64,framerate=30/1 ! \
+         pipefilter cmd=cat output-caps=\"video/x-raw,width=32,height=128\" ! appsink name=sink sync=false",
+    )
+    .expect("Failed to create pipeline")
+    .downcast::<gst::Pipeline>()
+    .unwrap();
+
+    let samples = pull_samples(&pipeline);
+    assert_eq!(samples.len(), 3);
+
+    // Fields missing from output-caps come from the input caps
+    let info = gst_video::VideoInfo::from_caps(samples[0].caps().unwrap()).unwrap();
+    assert_eq!(info.format(), gst_video::VideoFormat::Gray8);
+    assert_eq!((info.width(), info.height()), (32, 128));
+    assert_eq!(info.fps(), gst::Fraction::new(30, 1));
+}
+
+#[test]
+#[serial]
+fn test_pipefilter_subprocess_exits_early() {
+    init();
+
+    // Only the output of the first frame is written back
+    let pipeline = gst::parse::launch(
+        "videotestsrc num-buffers=10 ! video/x-raw,format=GRAY8,width=64,height=64 ! \
+         pipefilter cmd=\"head -c 4096\" ! fakesink",
+    )
+    .expect("Failed to create pipeline")
+    .downcast::<gst::Pipeline>()
+    .unwrap();
+
+    pipeline.set_state(gst::State::Playing).expect("Failed to set pipeline to Playing");
+    let msg = wait_for_message(
+        &pipeline,
+        gst::ClockTime::from_seconds(5),
+        &[gst::MessageType::Eos, gst::MessageType::Error],
+    );
+    assert!(matches!(msg.as_ref().map(|msg| msg.view()), Some(gst::MessageView::Error(..))));
+    pipeline.set_state(gst::State::Null).expect("Failed to set pipeline to Null");
+}
+
+#[test]
+#[serial]
+fn test_fifo_transport() {
+    init();
+
+    let fifo_path = create_temp_filepath("fifo");
+    let output_path = create_temp_filepath(".raw");
+    let (pipeline, sink) = build_small_frames_pipeline(&format!("cat {{fifo}} > {}", output_path), 3);
+    sink.set_property_from_str("transport", "fifo");
+    sink.set_property("fifo-path", &fifo_path);
+
+    pipeline.set_state(gst::State::Playing).expect("Failed to set pipeline to Playing");
+    let msg = wait_for_message(
+        &pipeline,
+        gst::ClockTime::from_seconds(5),
+        &[gst::MessageType::Eos, gst::MessageType::Error],
+    );
+    assert!(matches!(msg.as_ref().map(|msg| msg.view()), Some(gst::MessageView::Eos(..))));
+    pipeline.set_state(gst::State::Null).expect("Failed to set pipeline to Null");
+
+    let output = fs::metadata(&output_path).expect("Failed to stat output");
+    assert_eq!(output.len(), 3 * 64 * 64);
+
+    // The FIFO is removed when the element stops
+    assert!(!Path::new(&fifo_path).exists());
+
+    fs::remove_file(&output_path).ok();
+}
+
+#[test]
+#[serial]
+fn test_fifo_not_opened() {
+    init();
+
+    // The subprocess never opens the FIFO
+    let (pipeline, sink) = build_small_frames_pipeline("sleep 30", 3);
+    sink.set_property_from_str("transport", "fifo");
+    sink.set_property("fifo-open-timeout", gst::ClockTime::from_mseconds(500).nseconds());
+
+    let start = Instant::now();
+    pipeline.set_state(gst::State::Playing).expect("Failed to set pipeline to Playing");
+    let msg = wait_for_message(
+        &pipeline,
+        gst::ClockTime::from_seconds(5),
+        &[gst::MessageType::Eos, gst::MessageType::Error],
+    );
+    match msg.as_ref().map(|msg| msg.view()) {
+        Some(gst::MessageView::Error(err)) => {
+            assert!(err.debug().unwrap_or_default().contains("didn't open FIFO"));
+        }
+        other => panic!("Expected an error, got {:?}", other),
+    }
+    assert!(start.elapsed() < Duration::from_secs(5));
+    pipeline.set_state(gst::State::Null).expect("Failed to set pipeline to Null");
+}
+
+#[test]
+#[serial]
+fn test_fifo_path_quoted() {
+    init();
+
+    // Expanded into the shell command as a single word
+    let fifo_path = create_temp_filepath("it's a fifo");
+    let output_path = create_temp_filepath(".raw");
+    let (pipeline, sink) = build_small_frames_pipeline(&format!("cat {{fifo}} > {}", output_path), 3);
+    sink.set_property_from_str("transport", "fifo");
+    sink.set_property("fifo-path", &fifo_path);
+
+    pipeline.set_state(gst::State::Playing).expect("Failed to set pipeline to Playing");
+    let msg = wait_for_message(
+        &pipeline,
+        gst::ClockTime::from_seconds(5),
+        &[gst::MessageType::Eos, gst::MessageType::Error],
+    );
+    assert!(matches!(msg.as_ref().map(|msg| msg.view()), Some(gst::MessageView::Eos(..))));
+    pipeline.set_state(gst::State::Null).expect("Failed to set pipeline to Null");
+
+    let output = fs::metadata(&output_path).expect("Failed to stat output");
+    assert_eq!(output.len(), 3 * 64 * 64);
+
+    fs::remove_file(&output_path).ok();
+}
+
+#[test]
+#[serial]
+fn test_fifo_never_opened() {
+    init();
+
+    // The subprocess exits without ever opening the FIFO
+    let (pipeline, sink) = build_small_frames_pipeline("sleep 0.2", 3);
+    sink.set_property_from_str("transport", "fifo");
+
+    pipeline.set_state(gst::State::Playing).expect("Failed to set pipeline to Playing");
+    let msg = wait_for_message(
+        &pipeline,
+        gst::ClockTime::from_seconds(5),
+        &[gst::MessageType::Eos, gst::MessageType::Error],
+    );
+    match msg.as_ref().map(|msg| msg.view()) {
+        Some(gst::MessageView::Error(err)) => {
+            assert!(err.error().matches(gst::ResourceError::OpenWrite));
+        }
+        other => panic!("Expected an error, got {:?}", other),
+    }
+
+    pipeline.set_state(gst::State::Null).expect("Failed to set pipeline to Null");
+}
+
+#[test]
+#[serial]
+fn test_extra_fd_metadata() {
+    init();
+
+    let metadata_path = create_temp_filepath("jsonl");
+    let (pipeline, sink) = build_small_frames_pipeline(
+        &format!("cat {{fd3}} > {} & cat > /dev/null; wait", metadata_path),
+        3,
+    );
+    sink.set_property("extra-fds", gst::Array::new(["metadata"]));
+
+    pipeline.set_state(gst::State::Playing).expect("Failed to set pipeline to Playing");
+    let msg = wait_for_message(
+        &pipeline,
+        gst::ClockTime::from_seconds(5),
+        &[gst::MessageType::Eos, gst::MessageType::Error],
+    );
+    assert!(matches!(msg.as_ref().map(|msg| msg.view()), Some(gst::MessageView::Eos(..))));
+    pipeline.set_state(gst::State::Null).expect("Failed to set pipeline to Null");
+
+    // One JSON line per frame, with the timestamps of the buffer
+    let metadata = fs::read_to_string(&metadata_path).expect("Failed to read metadata");
+    let lines = metadata.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 3);
+    for (i, line) in lines.iter().enumerate() {
+        let value: serde_json::Value = serde_json::from_str(line).expect("Invalid JSON");
+        assert_eq!(value["seq"], i as u64);
+        assert!(value["pts"].is_u64());
+        assert!(value["duration"].is_u64());
+    }
+
+    fs::remove_file(&metadata_path).ok();
+}
+
+#[test]
+#[serial]
+fn test_extra_fd_metadata_stall_drop() {
+    init();
+
+    // Stdin is never read, frames that don't fit in the pipe buffer are dropped
+    let metadata_path = create_temp_filepath("jsonl");
+    let (pipeline, sink) = build_small_frames_pipeline(&format!("cat {{fd3}} > {} & exec sleep 30", metadata_path), 30);
+    sink.set_property("extra-fds", gst::Array::new(["metadata"]));
+    sink.set_property("write-timeout", 20_000_000u64);
+    sink.set_property_from_str("stall-action", "drop");
+    sink.set_property("eos-timeout", 100_000_000u64);
+
+    pipeline.set_state(gst::State::Playing).expect("Failed to set pipeline to Playing");
+    let msg = wait_for_message(
+        &pipeline,
+        gst::ClockTime::from_seconds(5),
+        &[gst::MessageType::Eos, gst::MessageType::Error],
+    );
+    assert!(matches!(msg.as_ref().map(|msg| msg.view()), Some(gst::MessageView::Eos(..))));
+
+    let stats = sink.property::<gst::Structure>("stats");
+    let written = stats.get::<u64>("buffers-written").unwrap();
+    assert!(stats.get::<u64>("buffers-dropped").unwrap() > 0);
+    pipeline.set_state(gst::State::Null).expect("Failed to set pipeline to Null");
+
+    // Dropped frames have no metadata line, cat may still be copying the last lines
+    let deadline = Instant::now() + Duration::from_secs(2);
+    let mut lines = 0;
+    while Instant::now() < deadline {
+        lines = fs::read_to_string(&metadata_path).expect("Failed to read metadata").lines().count() as u64;
+        if lines >= written {
+            break;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    assert_eq!(lines, written);
+
+    fs::remove_file(&metadata_path).ok();
+}
+
+#[test]
+#[serial]
+fn test_extra_fd_messages() {
+    init();
+
+    let (pipeline, sink) = build_small_frames_pipeline("echo detected >&4; cat > /dev/null", 3);
+    sink.set_property("extra-fds", gst::Array::new(["metadata", "messages"]));
+
+    pipeline.set_state(gst::State::Playing).expect("Failed to set pipeline to Playing");
+    let messages = collect_messages(
+        &pipeline,
+        gst::ClockTime::from_seconds(5),
+        &[gst::MessageType::Eos, gst::MessageType::Error],
+    );
+    assert_eq!(messages.last().map(|msg| msg.type_()), Some(gst::MessageType::Eos));
+    pipeline.set_state(gst::State::Null).expect("Failed to set pipeline to Null");
+
+    // The metadata fd isn't read, its lines fit in the pipe buffer
+    let lines = element_messages(&messages, "subprocess-message");
+    assert_eq!(lines.len(), 1);
+    assert_eq!(lines[0].get::<i32>("fd").unwrap(), 4);
+    assert_eq!(lines[0].get::<&str>("line").unwrap(), "detected");
+}
+
+#[test]
+#[serial]
+fn test_extra_fds_invalid() {
+    init();
+
+    let (pipeline, sink) = build_small_frames_pipeline("cat > /dev/null", 3);
+    sink.set_property("extra-fds", gst::Array::new(["results"]));
+
+    assert!(pipeline.set_state(gst::State::Playing).is_err());
+    pipeline.set_state(gst::State::Null).expect("Failed to set pipeline to Null");
+}
+
+// Accepts `connections` connections on `listener` one after the other, reading up to `limit`
+// bytes from each before closing it. Returns the bytes received on every connection.
+fn serve_unix_socket(
+    listener: std::os::unix::net::UnixListener,
+    connections: usize,
+    limit: u64,
+) -> thread::JoinHandle<Vec<Vec<u8>>> {
+    thread::spawn(move || {
+        (0..connections)
+            .map(|_| {
+                let (stream, _) = listener.accept().expect("Failed to accept connection");
+                let mut data = Vec::new();
+                stream.take(limit).read_to_end(&mut data).expect("Failed to read from connection");
+                data
+            })
+            .collect()
+    })
+}
+
+#[test]
+#[serial]
+fn test_unix_socket_transport() {
+    init();
+
+    let socket_path = create_temp_filepath("sock");
+    let listener = std::os::unix::net::UnixListener::bind(&socket_path).expect("Failed to bind socket");
+    let server = serve_unix_socket(listener, 1, u64::MAX);
+
+    let (pipeline, sink) = build_small_frames_pipeline("", 3);
+    sink.set_property_from_str("transport", "unix-socket");
+    sink.set_property("socket-path", &socket_path);
+
+    pipeline.set_state(gst::State::Playing).expect("Failed to set pipeline to Playing");
+    let msg = wait_for_message(
+        &pipeline,
+        gst::ClockTime::from_seconds(5),
+        &[gst::MessageType::Eos, gst::MessageType::Error],
+    );
+    assert!(matches!(msg.as_ref().map(|msg| msg.view()), Some(gst::MessageView::Eos(..))));
+    pipeline.set_state(gst::State::Null).expect("Failed to set pipeline to Null");
+
+    // The connection is closed on EOS
+    let received = server.join().unwrap();
+    assert_eq!(received[0].len(), 3 * 64 * 64);
+
+    fs::remove_file(&socket_path).ok();
+}
+
+#[test]
+#[serial]
+fn test_unix_socket_reconnect() {
+    use std::os::linux::net::SocketAddrExt;
+    use std::os::unix::net::{SocketAddr, UnixListener};
+
+    init();
+
+    let name = format!("gst-subprocess-pipe-test-{}", process::id());
+    let addr = SocketAddr::from_abstract_name(name.as_bytes()).unwrap();
+    let listener = UnixListener::bind_addr(&addr).expect("Failed to bind socket");
+
+    // The first connection goes away after a single frame
+    let frame_size = 64 * 64;
+    let server = serve_unix_socket(listener, 2, frame_size);
+
+    let (pipeline, sink) = build_small_frames_pipeline("", 30);
+    sink.set_property_from_str("transport", "unix-socket");
+    sink.set_property("socket-path", format!("@{}", name));
+    sink.set_property("restart-backoff", 10_000_000u64);
+
+    pipeline.set_state(gst::State::Playing).expect("Failed to set pipeline to Playing");
+    let messages = collect_messages(
+        &pipeline,
+        gst::ClockTime::from_seconds(10),
+        &[gst::MessageType::Eos, gst::MessageType::Error],
+    );
+    assert_eq!(messages.last().map(|msg| msg.type_()), Some(gst::MessageType::Eos));
+    let stats = sink.property::<gst::Structure>("stats");
+    pipeline.set_state(gst::State::Null).expect("Failed to set pipeline to Null");
+
+    let reconnected = element_messages(&messages, "socket-reconnected");
+    assert_eq!(reconnected.len(), 1);
+    assert_eq!(reconnected[0].get::<u32>("restart-count").unwrap(), 1);
+    assert_eq!(stats.get::<u32>("restarts").unwrap(), 1);
+
+    // The second connection only gets whole frames
+    let received = server.join().unwrap();
+    assert_eq!(received[0].len() as u64, frame_size);
+    assert!(!received[1].is_empty());
+    assert_eq!(received[1].len() as u64 % frame_size, 0);
+}
+
+#[test]
+#[serial]
+fn test_shm_transport() {
+    use gstsubprocesspipe::length_prefixed::{Reader, RecordKind};
+    use gstsubprocesspipe::shm::Ring;
+    use std::os::fd::OwnedFd;
+
+    init();
+
+    // Reference frames written to stdin
+    let reference_path = create_temp_filepath("raw");
+    let (pipeline, _sink) = build_small_frames_pipeline(&format!("cat > {}", reference_path), 3);
+    pipeline.set_state(gst::State::Playing).expect("Failed to set pipeline to Playing");
+    wait_for_message(&pipeline, gst::ClockTime::from_seconds(5), &[gst::MessageType::Eos]).expect("No EOS");
+    pipeline.set_state(gst::State::Null).expect("Failed to set pipeline to Null");
+    let reference = fs::read(&reference_path).expect("Failed to read reference frames");
+
+    // The subprocess doesn't release the slots, so that the ring holds all frames at the end
+    let records_path = create_temp_filepath("records");
+    let ring_path = create_temp_filepath("ring");
+    let (pipeline, sink) = build_small_frames_pipeline(
+        &format!("cat > {}; cat /dev/fd/{{shm_fd}} > {}", records_path, ring_path),
+        3,
+    );
+    sink.set_property_from_str("transport", "shm");
+
+    pipeline.set_state(gst::State::Playing).expect("Failed to set pipeline to Playing");
+    let msg = wait_for_message(
+        &pipeline,
+        gst::ClockTime::from_seconds(5),
+        &[gst::MessageType::Eos, gst::MessageType::Error],
+    );
+    assert!(matches!(msg.as_ref().map(|msg| msg.view()), Some(gst::MessageView::Eos(..))));
+    pipeline.set_state(gst::State::Null).expect("Failed to set pipeline to Null");
+
+    let records = Reader::new(File::open(&records_path).expect("Failed to open records"))
+        .collect::<Result<Vec<_>, _>>()
+        .expect("Failed to read records");
+    assert_eq!(records.len(), 4);
+    assert_eq!(records[0].header.kind, RecordKind::Caps);
+
+    let ring_file = fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(&ring_path)
+        .expect("Failed to open ring buffer");
+    let ring = Ring::from_fd(OwnedFd::from(ring_file)).expect("Invalid ring buffer");
+    assert_eq!(ring.slot_count(), 4);
+    assert_eq!(ring.slot_size(), 64 * 64);
+
+    for (i, record) in records[1..].iter().enumerate() {
+        assert_eq!(record.header.kind, RecordKind::Slot);
+        assert_eq!(record.slot(), Some((i as u32, 64 * 64)));
+
+        let frame = ring.frame(record).expect("Invalid slot record");
+        assert_eq!(&frame[..], &reference[i * 64 * 64..(i + 1) * 64 * 64]);
+    }
+
+    fs::remove_file(&reference_path).ok();
+    fs::remove_file(&records_path).ok();
+    fs::remove_file(&ring_path).ok();
+}
+
+#[test]
+#[serial]
+fn test_shm_slots_full() {
+    init();
+
+    // A subprocess that never releases a slot stalls the sink once all slots are filled
+    let (pipeline, sink) = build_small_frames_pipeline("cat > /dev/null", 10);
+    sink.set_property_from_str("transport", "shm");
+    sink.set_property("shm-slots", 2u32);
+    sink.set_property("write-timeout", 50_000_000u64);
+    sink.set_property_from_str("stall-action", "drop");
+
+    pipeline.set_state(gst::State::Playing).expect("Failed to set pipeline to Playing");
+    let messages = collect_messages(
+        &pipeline,
+        gst::ClockTime::from_seconds(5),
+        &[gst::MessageType::Eos, gst::MessageType::Error],
+    );
+    assert_eq!(messages.last().map(|msg| msg.type_()), Some(gst::MessageType::Eos));
+
+    let stats = sink.property::<gst::Structure>("stats");
+    pipeline.set_state(gst::State::Null).expect("Failed to set pipeline to Null");
+
+    assert_eq!(stats.get::<u64>("buffers-written").unwrap(), 2);
+    assert_eq!(stats.get::<u64>("buffers-dropped").unwrap(), 8);
+    assert_eq!(element_messages(&messages, "subprocess-stalled").len(), 8);
+}
+
+#[test]
+#[serial]
+fn test_shm_dropped_records_release_slots() {
+    init();
+
+    // Releases the slot of every record it reads, after leaving stdin full for a while. The
+    // 4 KiB pipe holds fewer records than there are slots, so frames are dropped after their
+    // slot was filled, and then while all slots are taken.
+    let ring_path = create_temp_filepath("ring");
+    let consumer = format!(
+        "sleep 4; \
+         while h=$(dd bs=48 count=1 iflag=fullblock 2>/dev/null | od -An -tu4 -v); [ -n \"$h\" ]; do \
+           set -- $h; \
+           p=$(dd bs=$3 count=1 iflag=fullblock 2>/dev/null | od -An -tu4 -v); \
+           if [ $(($2 >> 16)) -eq 2 ]; then \
+             set -- $p; \
+             head -c 4 /dev/zero | dd of=/dev/fd/{{shm_fd}} bs=4 seek=$((8 + $1)) conv=notrunc 2>/dev/null; \
+           fi; \
+         done; \
+         cat /dev/fd/{{shm_fd}} > {}",
+        ring_path
+    );
+    let slots = 150u32;
+    let (pipeline, sink) = build_small_frames_pipeline(&consumer, 400);
+    sink.set_property("sync", false);
+    sink.set_property_from_str("transport", "shm");
+    sink.set_property("shm-slots", slots);
+    sink.set_property("pipe-size", 4096u32);
+    sink.set_property("write-timeout", 20_000_000u64);
+    sink.set_property_from_str("stall-action", "drop");
+    sink.set_property("eos-timeout", gst::ClockTime::from_seconds(20).nseconds());
+
+    pipeline.set_state(gst::State::Playing).expect("Failed to set pipeline to Playing");
+    let msg = wait_for_message(
+        &pipeline,
+        gst::ClockTime::from_seconds(30),
+        &[gst::MessageType::Eos, gst::MessageType::Error],
+    );
+    assert!(matches!(msg.as_ref().map(|msg| msg.view()), Some(gst::MessageView::Eos(..))));
+
+    let stats = sink.property::<gst::Structure>("stats");
+    pipeline.set_state(gst::State::Null).expect("Failed to set pipeline to Null");
+
+    // Streaming went on after more frames than slots were dropped
+    assert!(stats.get::<u64>("buffers-dropped").unwrap() > slots as u64);
+    assert!(stats.get::<u64>("buffers-written").unwrap() > 128);
+    assert_eq!(
+        stats.get::<u64>("bytes-written").unwrap(),
+        stats.get::<u64>("buffers-written").unwrap() * 64 * 64
+    );
+
+    // No slot is left filled without a record
+    let ring = fs::read(&ring_path).expect("Failed to read ring buffer");
+    let states = ring[32..32 + 4 * slots as usize]
+        .chunks(4)
+        .map(|state| u32::from_le_bytes(state.try_into().unwrap()))
+        .collect::<Vec<_>>();
+    assert_eq!(states, vec![0; slots as usize]);
+
+    fs::remove_file(&ring_path).ok();
+}
+
+#[test]
+#[serial]
+fn test_vmsplice() {
+    init();
+
+    // Moving frames, so that reused frame memory would show up in the output
+    let run = |cmd: &str, properties: &str| {
+        let pipeline = gst::parse::launch(&format!(
+            "videotestsrc num-buffers=10 pattern=ball ! video/x-raw,format=GRAY8,width=64,height=64 ! \
+             videopipesink name=sink cmd=\"{}\" {}",
+            cmd, properties
+        ))
+        .expect("Failed to create pipeline")
+        .downcast::<gst::Pipeline>()
+        .unwrap();
+
+        pipeline.set_state(gst::State::Playing).expect("Failed to set pipeline to Playing");
+        let msg = wait_for_message(
+            &pipeline,
+            gst::ClockTime::from_seconds(5),
+            &[gst::MessageType::Eos, gst::MessageType::Error],
+        );
+        assert!(matches!(msg.as_ref().map(|msg| msg.view()), Some(gst::MessageView::Eos(..))));
+        pipeline.set_state(gst::State::Null).expect("Failed to set pipeline to Null");
+    };
+
+    let reference_path = create_temp_filepath("raw");
+    run(&format!("cat > {}", reference_path), "");
+    let reference = fs::read(&reference_path).expect("Failed to read reference frames");
+    assert_eq!(reference.len(), 10 * 64 * 64);
+
+    // The subprocess only starts reading once all frames are in the enlarged pipe
+    for properties in [
+        "vmsplice=true pipe-size=1048576",
+        "vmsplice=true pipe-size=1048576 max-queued-buffers=4",
+        "vmsplice=true framing=y4m",
+    ] {
+        let output_path = create_temp_filepath("raw");
+        run(&format!("sleep 0.3; cat > {}", output_path), properties);
+
+        let output = fs::read(&output_path).expect("Failed to read output");
+        if properties.contains("y4m") {
+            assert!(output.starts_with(b"YUV4MPEG2 "));
+            assert_eq!(output.windows(6).filter(|window| window == b"FRAME\n").count(), 10);
+        } else {
+            assert!(output == reference, "Output differs with {}", properties);
+        }
+
+        fs::remove_file(&output_path).ok();
+    }
+
+    fs::remove_file(&reference_path).ok();
+}